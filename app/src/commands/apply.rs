@@ -2,13 +2,17 @@ use super::ComtryaCommand;
 use crate::Runtime;
 use clap::Parser;
 use comfy_table::{Cell, ContentArrangement, Table};
-use comtrya_lib::contexts::to_rhai;
-use comtrya_lib::manifests::{load, Manifest};
-use core::panic;
-use petgraph::{visit::DfsPostOrder, Graph};
-use rhai::Engine;
+use comtrya_lib::contexts::{to_rhai, Contexts};
+use comtrya_lib::manifests::{
+    load, read, validate, with_deps, Dependencies, Manifest, ManifestSource,
+};
+use comtrya_lib::rhai_functions;
+use comtrya_lib::values::Value;
 use std::path::PathBuf;
-use std::{collections::HashMap, ops::Deref};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Deref,
+};
 use tracing::{debug, error, info, instrument, span, trace, warn};
 
 #[derive(Parser, Debug)]
@@ -25,6 +29,10 @@ pub(crate) struct Apply {
     /// Define label selector
     #[arg(short, long)]
     pub label: Option<String>,
+
+    /// Validate every manifest first, and apply nothing if there's a problem
+    #[arg(long)]
+    strict: bool,
 }
 
 impl Apply {
@@ -64,7 +72,7 @@ impl Apply {
 
         println!("Load manifests from path: {:#?}", manifest_path);
 
-        let manifests = load(manifest_path, contexts);
+        let manifests = load(manifest_path, &runtime.config.discovery, contexts)?;
 
         let mut table = Table::new();
         table
@@ -88,230 +96,250 @@ impl ComtryaCommand for Apply {
     fn execute(&self, runtime: &Runtime) -> anyhow::Result<()> {
         let contexts = &runtime.contexts;
         let manifest_path = self.manifest_path(&runtime)?;
-        let manifests = load(manifest_path, contexts);
-
-        // Build DAG
-        let mut dag: Graph<Manifest, u32, petgraph::Directed> = Graph::new();
-
-        let manifest_root = Manifest {
-            r#where: None,
-            root_dir: None,
-            dag_index: None,
-            name: None,
-            depends: vec![],
-            actions: vec![],
-            ..Default::default()
-        };
 
-        let root_index = dag.add_node(manifest_root);
+        // Manifests are rendered right before they run, so they can use the
+        // outputs of their dependencies. Only what's needed to order them is
+        // read upfront.
+        let sources = read(&manifest_path, &runtime.config.discovery)?;
 
-        let manifests: HashMap<String, Manifest> = manifests
-            .into_iter()
-            .map(|(name, mut manifest)| {
-                let abc = dag.add_node(manifest.clone());
+        if self.strict {
+            super::validate::report(&validate(&sources, contexts))?;
+        }
 
-                manifest.dag_index = Some(abc);
-                dag.add_edge(root_index, abc, 0);
+        let sources: HashMap<String, ManifestSource> = sources
+            .into_iter()
+            .map(|source| (source.name.clone(), source))
+            .collect();
 
-                (name, manifest)
+        let manifests: HashMap<String, Manifest> = sources
+            .values()
+            .filter_map(|source| match source.header(contexts) {
+                Ok(manifest) => Some((source.name.clone(), manifest)),
+                Err(err) => {
+                    error!(
+                        "Manifest '{}' in file with path '{}' cannot be parsed. Reason: {:#}",
+                        source.name,
+                        source.path.display(),
+                        err
+                    );
+                    None
+                }
             })
             .collect();
 
-        for (name, manifest) in manifests.iter() {
-            manifest.depends.iter().for_each(|dependency| {
-                let (local_dependency_prefix, _) = name.rsplit_once('.').unwrap_or((name, ""));
-
-                let resolved_dependency_name =
-                    dependency.replace("./", format!("{}.", local_dependency_prefix).as_str());
-
-                let m1 = match manifests.get(&resolved_dependency_name) {
-                    Some(manifest) => manifest,
-                    None => {
-                        error!(
-                            message = "Unresolved dependency",
-                            dependency = resolved_dependency_name.as_str()
-                        );
-
-                        return;
-                    }
-                };
-
-                trace!(
-                    message = "Dependency Registered",
-                    from = name.as_str(),
-                    to = m1.name.as_deref().unwrap_or("cannot extract name"),
-                );
-
-                if let (Some(from), Some(to)) = (manifest.dag_index, m1.dag_index) {
-                    dag.add_edge(from, to, 0);
-                } else {
-                    error!(message = "Cannot add dependency, missing dag index");
-                }
-            });
-        }
+        let dependencies = Dependencies::resolve(&manifests);
 
         let clone_m = self.manifests.clone();
+        let profile = runtime.config.profile();
 
-        let run_manifests = if self.manifests.is_empty() {
-            // No manifests specified on command line, so run everything
-            vec![String::from("")]
-        } else {
+        let run_manifests = if !self.manifests.is_empty() {
             // Run subset
             manifests
                 .keys()
                 .filter(|z| clone_m.contains(z))
                 .cloned()
                 .collect::<Vec<String>>()
+        } else if let Some(profile) = profile.filter(|profile| !profile.manifests.is_empty()) {
+            // Run the subset selected by the profile
+            manifests
+                .keys()
+                .filter(|name| profile.selects(name))
+                .cloned()
+                .collect::<Vec<String>>()
+        } else {
+            // No manifests specified on command line, so run everything
+            manifests.keys().cloned().collect::<Vec<String>>()
         };
 
+        let order = dependencies.order(&run_manifests)?;
+
         let dry_run = self.dry_run;
 
-        let engine = Engine::new();
-        let mut scope = to_rhai(contexts);
-
-        run_manifests.iter().for_each(|manifest| {
-            let start = if manifest.eq(&String::from("")) {
-                root_index
-            } else if let Some(dag_index) = manifests
-                .get(manifest)
-                .and_then(|manifest| manifest.dag_index)
-            {
-                dag_index
-            } else {
-                // FIXME: Don't panic here. Find a better way to handle this.
-                panic!("Cannot find manifest in DAG");
+        // The rendered outputs of every manifest that ran, by name
+        let mut outputs: BTreeMap<String, BTreeMap<String, Value>> = BTreeMap::new();
+
+        for name in order.iter() {
+            let Some(m1) = manifests.get(name) else {
+                continue;
             };
 
-            let mut dfs = DfsPostOrder::new(&dag, start);
+            let span_manifest = span!(tracing::Level::INFO, "", manifest = name).entered();
+
+            let mut successful = true;
 
-            while let Some(visited) = dfs.next(&dag) {
-                if dag.node_weight(visited).is_none() {
+            if let Some(label) = self.label.as_ref() {
+                if !m1.labels.contains(label) {
                     info!(
-                        message = "Skipping manifest, not found in DAG",
-                        index = visited.index()
+                        message = "Skipping manifest, label not found",
+                        label = label.as_str()
                     );
+                    continue;
                 }
+            } else if let Some(profile) = profile {
+                if !profile.labelled(&m1.labels) {
+                    info!(
+                        message = "Skipping manifest, none of the profile labels found",
+                        labels = profile.labels.join(",").as_str()
+                    );
+                    continue;
+                }
+            }
 
-                // .unwrap() is safe here, because we just checked that the node exists
-                let m1 = dag.node_weight(visited).unwrap();
+            let deps: BTreeMap<String, BTreeMap<String, Value>> = dependencies
+                .depends
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter_map(|dependency| {
+                    outputs
+                        .get(dependency)
+                        .map(|outputs| (dependency.clone(), outputs.clone()))
+                })
+                .collect();
+
+            let Some(source) = sources.get(name) else {
+                continue;
+            };
 
-                // Root manifest, nothing to do.
-                if m1.name.is_none() {
+            let m1 = match source.render(&with_deps(contexts, &deps)) {
+                Ok(manifest) => manifest,
+                Err(err) => {
+                    error!("Manifest '{}' cannot be rendered: {:#}", name, err);
+                    span_manifest.exit();
                     continue;
                 }
+            };
 
-                let span_manifest = span!(
-                    tracing::Level::INFO,
-                    "",
-                    manifest = m1.name.as_deref().unwrap_or("Cannot extract name"),
-                )
-                .entered();
-
-                let mut successful = true;
+            let mut contexts = m1.contexts(&with_deps(contexts, &deps));
+            let mut scope = to_rhai(&contexts);
 
-                if let Some(label) = self.label.as_ref() {
-                    if !m1.labels.contains(label) {
-                        info!(
-                            message = "Skipping manifest, label not found",
-                            label = label.as_str()
+            if let Some(where_condition) = &m1.r#where {
+                let where_result = match rhai_functions::eval::<bool>(&mut scope, where_condition) {
+                    Ok(result) => {
+                        debug!(
+                            "Result of 'where' condition '{}' -> '{}'",
+                            where_condition, result
                         );
-                        continue;
-                    }
-                }
 
-                if let Some(where_condition) = &m1.r#where {
-                    let where_result =
-                        match engine.eval_with_scope::<bool>(&mut scope, where_condition) {
-                            Ok(result) => {
-                                debug!(
-                                    "Result of 'where' condition '{}' -> '{}'",
-                                    where_condition, result
-                                );
-
-                                result
-                            }
-                            Err(err) => {
-                                warn!("'where' condition '{}' failed: {}", where_condition, err);
-                                false
-                            }
-                        };
-
-                    if !where_result {
-                        info!("Skip manifest, because 'where' conditions were false!");
-                        span_manifest.exit();
-                        continue;
+                        result
+                    }
+                    Err(err) => {
+                        warn!("'where' condition '{}' failed: {}", where_condition, err);
+                        false
                     }
+                };
+
+                if !where_result {
+                    info!("Skip manifest, because 'where' conditions were false!");
+                    span_manifest.exit();
+                    continue;
                 }
+            }
 
-                for action in m1.actions.iter() {
-                    let span_action = span!(tracing::Level::INFO, "", %action).entered();
+            for action in m1.actions.iter() {
+                let span_action = span!(tracing::Level::INFO, "", %action).entered();
 
-                    let action = action.inner_ref();
+                // Exported again for each action, as `command.run` removes its
+                // own env once it ran
+                let _env = m1.export_env();
 
-                    let plan = match action.plan(m1, contexts) {
-                        Ok(steps) => steps,
-                        Err(err) => {
-                            info!("Action failed to get plan: {:?}", err);
-                            successful = false;
-                            continue;
-                        }
-                    };
-
-                    let mut steps = plan
-                        .into_iter()
-                        .filter(|step| step.do_initializers_allow_us_to_run())
-                        .filter(|step| match step.atom.plan() {
-                            Ok(outcome) => outcome.should_run,
-                            Err(_) => false,
-                        })
-                        .peekable();
-
-                    if steps.peek().is_none() {
-                        info!("nothing to be done to reconcile action");
-                        span_action.exit();
+                let action = action.inner_ref();
+
+                let plan = match action.plan(&m1, &contexts) {
+                    Ok(steps) => steps,
+                    Err(err) => {
+                        info!("Action failed to get plan: {:?}", err);
+                        successful = false;
                         continue;
                     }
+                };
 
-                    for mut step in steps {
-                        if dry_run {
-                            continue;
-                        }
+                let mut steps = plan
+                    .into_iter()
+                    .filter(|step| step.do_initializers_allow_us_to_run())
+                    .filter(|step| match step.atom.plan() {
+                        Ok(outcome) => outcome.should_run,
+                        Err(_) => false,
+                    })
+                    .peekable();
+
+                if steps.peek().is_none() {
+                    info!("nothing to be done to reconcile action");
+                    register(&mut contexts, action.register(), "");
+                    span_action.exit();
+                    continue;
+                }
 
-                        match step.atom.execute() {
-                            Ok(_) => (),
-                            Err(err) => {
-                                debug!("Atom failed to execute: {:?}", err);
-                                successful = false;
-                                break;
-                            }
-                        }
+                let mut output = None;
+
+                for mut step in steps {
+                    if dry_run {
+                        continue;
+                    }
 
-                        if !step.do_finalizers_allow_us_to_continue() {
-                            debug!("Finalizers won't allow us to continue with this action");
+                    match step.atom.execute() {
+                        Ok(_) => output = Some(step.atom.output_string()),
+                        Err(err) => {
+                            debug!("Atom failed to execute: {:?}", err);
                             successful = false;
                             break;
                         }
                     }
-                    info!("{}", action.summarize());
-                    span_action.exit();
-                }
 
-                if dry_run {
-                    span_manifest.exit();
-                    continue;
+                    if !step.do_finalizers_allow_us_to_continue() {
+                        debug!("Finalizers won't allow us to continue with this action");
+                        successful = false;
+                        break;
+                    }
                 }
 
-                if !successful {
-                    error!("Failed");
-                    span_manifest.exit();
-                    break;
+                // Actions that didn't run, as in a dry run, register an empty
+                // output, so the outputs using it can still be rendered
+                register(
+                    &mut contexts,
+                    action.register(),
+                    output.as_deref().unwrap_or_default(),
+                );
+
+                info!("{}", action.summarize());
+                span_action.exit();
+            }
+
+            if successful {
+                match source.outputs(&m1, &contexts) {
+                    Ok(rendered) => {
+                        outputs.insert(name.to_string(), rendered);
+                    }
+                    Err(err) => warn!("{:#}", err),
                 }
+            }
+
+            if dry_run {
+                span_manifest.exit();
+                continue;
+            }
 
-                info!("Completed");
+            if !successful {
+                error!("Failed");
                 span_manifest.exit();
+                break;
             }
-        });
+
+            info!("Completed");
+            span_manifest.exit();
+        }
 
         Ok(())
     }
 }
+
+/// Makes the output of an action with `register: <name>` available to the
+/// rest of its manifest as `registered.<name>`, trimmed
+fn register(contexts: &mut Contexts, name: Option<&str>, output: &str) {
+    if let Some(name) = name {
+        contexts
+            .entry(String::from("registered"))
+            .or_default()
+            .insert(name.to_string(), Value::from(output.trim()));
+    }
+}
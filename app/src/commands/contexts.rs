@@ -2,8 +2,12 @@ use super::ComtryaCommand;
 use crate::Runtime;
 use colored::Colorize;
 use comfy_table::{presets::NOTHING, Attribute, Cell, ContentArrangement, Table};
+use comtrya_lib::contexts::{get, variables::explain};
+use comtrya_lib::values::Value;
+use serde::Serialize;
 
-use clap::Parser;
+use anyhow::anyhow;
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command()]
@@ -11,10 +15,130 @@ pub(crate) struct Contexts {
     /// Show the values of the contexts
     #[arg(long)]
     show_values: bool,
+
+    /// Show where a value comes from, e.g. `variables.email`
+    #[arg(long, value_name = "KEY")]
+    explain: Option<String>,
+
+    /// Output format
+    #[arg(long, value_enum, global = true, default_value_t = Format::Table)]
+    format: Format,
+
+    #[command(subcommand)]
+    command: Option<ContextsCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum ContextsCommand {
+    /// Print a single value, e.g. `os.name` or `variables.git.email`
+    Get { key: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Yaml,
+}
+
+fn serialize<T: Serialize>(format: Format, value: &T) -> anyhow::Result<String> {
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(value)?,
+        Format::Yaml => serde_yml::to_string(value)?.trim_end().to_string(),
+        Format::Table => unreachable!("tables aren't serialized"),
+    })
+}
+
+impl Contexts {
+    fn get(&self, runtime: &Runtime, key: &str) -> anyhow::Result<()> {
+        // A bare prefix, e.g. `os`, prints the whole context
+        let value = match runtime.contexts.get(key) {
+            Some(context) => Value::from(context.clone()),
+            None => get(&runtime.contexts, key)
+                .cloned()
+                .ok_or_else(|| anyhow!("{} is not defined", key))?,
+        };
+
+        // Plain strings are printed as is, so scripts don't have to unquote them
+        match (self.format, &value) {
+            (Format::Table, Value::String(string)) => println!("{}", string),
+            (Format::Table, value) => println!("{}", serde_json::to_string(value)?),
+            (format, value) => println!("{}", serialize(format, value)?),
+        }
+
+        Ok(())
+    }
+
+    fn explain(&self, runtime: &Runtime, key: &str) -> anyhow::Result<()> {
+        let (context, name) = key
+            .split_once('.')
+            .ok_or_else(|| anyhow!("Expected a key like `variables.name`, got `{}`", key))?;
+
+        if context != "variables" {
+            let value =
+                get(&runtime.contexts, key).ok_or_else(|| anyhow!("{} is not defined", key))?;
+
+            println!("{} is provided by the {} context", key.bold(), context);
+            println!("{}", value);
+
+            return Ok(());
+        }
+
+        let layers = explain(&runtime.config, name)?;
+
+        if layers.is_empty() {
+            return Err(anyhow!("{} is not defined in any variables layer", key));
+        }
+
+        println!("{}", key.underline().bold());
+
+        let mut table = Table::new();
+        table
+            .load_preset(NOTHING)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+
+        let winner = layers.len() - 1;
+
+        for (index, (layer, value)) in layers.into_iter().enumerate() {
+            let value = strip_ansi_escapes::strip(value.to_string());
+            let value = String::from_utf8(value).unwrap_or_default();
+
+            if index == winner {
+                table.add_row(vec![
+                    Cell::new(layer).add_attribute(Attribute::Bold),
+                    Cell::new(value).add_attribute(Attribute::Bold),
+                    Cell::new("<- wins"),
+                ]);
+            } else {
+                table.add_row(vec![
+                    Cell::new(layer).add_attribute(Attribute::CrossedOut),
+                    Cell::new(value).add_attribute(Attribute::CrossedOut),
+                ]);
+            }
+        }
+
+        println!("{table}");
+
+        Ok(())
+    }
 }
 
 impl ComtryaCommand for Contexts {
     fn execute(&self, runtime: &Runtime) -> anyhow::Result<()> {
+        if let Some(key) = &self.explain {
+            return self.explain(runtime, key);
+        }
+
+        if let Some(ContextsCommand::Get { key }) = &self.command {
+            return self.get(runtime, key);
+        }
+
+        if self.format != Format::Table {
+            println!("{}", serialize(self.format, &runtime.contexts)?);
+
+            return Ok(());
+        }
+
         for (name, context) in runtime.contexts.iter() {
             println!("{}", name.to_string().underline().bold());
 
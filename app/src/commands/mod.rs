@@ -10,6 +10,9 @@ pub(crate) use contexts::Contexts;
 mod gen_completions;
 pub(crate) use gen_completions::GenCompletions;
 
+mod validate;
+pub(crate) use validate::Validate;
+
 use crate::Runtime;
 
 pub trait ComtryaCommand {
@@ -0,0 +1,50 @@
+use super::ComtryaCommand;
+use crate::Runtime;
+use anyhow::anyhow;
+use clap::Parser;
+use comtrya_lib::manifests::{read, validate, Diagnostic};
+
+#[derive(Parser, Debug)]
+#[command()]
+pub(crate) struct Validate {}
+
+impl ComtryaCommand for Validate {
+    fn execute(&self, runtime: &Runtime) -> anyhow::Result<()> {
+        let first_manifest_path = runtime.config.manifest_paths.first().ok_or_else(|| {
+            anyhow!(
+                "No manifest paths found in config file, please add at least one path to your manifests"
+            )
+        })?;
+
+        let manifest_path = crate::manifests::resolve(first_manifest_path).ok_or_else(|| {
+            anyhow!(
+                "Manifest location, {:?}, could be resolved",
+                first_manifest_path
+            )
+        })?;
+
+        let sources = read(&manifest_path, &runtime.config.discovery)?;
+        report(&validate(&sources, &runtime.contexts))?;
+
+        println!("No problems found in {} manifests", sources.len());
+
+        Ok(())
+    }
+}
+
+/// Prints every problem, failing when there's any
+pub(crate) fn report(diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    Err(anyhow!(
+        "Found {} problem{} in the manifests",
+        diagnostics.len(),
+        if diagnostics.len() == 1 { "" } else { "s" }
+    ))
+}
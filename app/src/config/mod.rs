@@ -1,9 +1,17 @@
 use crate::commands;
 use clap::{Parser, Subcommand};
-use std::error::Error;
 
 use anyhow::{anyhow, Context, Result};
 pub use comtrya_lib::config::Config;
+use comtrya_lib::contexts::{
+    build_contexts_for,
+    os::{parse_os_override, OsOverride},
+    to_rhai,
+    variables::{define, load_vars_file, parse_define},
+};
+use comtrya_lib::manifests;
+use comtrya_lib::rhai_functions;
+use comtrya_lib::values::Value;
 use std::{
     path::{Path, PathBuf},
     vec,
@@ -25,8 +33,25 @@ pub struct GlobalArgs {
     #[arg(long)]
     pub no_color: bool,
 
-    #[arg(short = 'D', long, value_parser = parse_key_val::<String, String>)]
-    pub defines: Vec<(String, String)>,
+    /// Define a variable, e.g. `-D git.email=sam@sgc.mil` or `-D jobs:int=8`
+    #[arg(short = 'D', long, value_name = "KEY[:TYPE]=VALUE", value_parser = parse_define)]
+    pub defines: Vec<(String, Value)>,
+
+    /// Load variables from a YAML file, can be given multiple times
+    #[arg(long, value_name = "PATH")]
+    pub vars_file: Vec<PathBuf>,
+
+    /// Ignore cached contexts and compute them again
+    #[arg(long)]
+    pub refresh_contexts: bool,
+
+    /// Pretend to be another OS, e.g. `--os-override Ubuntu:24.04`
+    #[arg(long, value_name = "TYPE[:VERSION]", value_parser = parse_os_override)]
+    pub os_override: Option<OsOverride>,
+
+    /// Select a profile from the configuration
+    #[arg(short = 'p', long, env = "COMTRYA_PROFILE")]
+    pub profile: Option<String>,
 
     /// Debug & tracing mode (-v, -vv)
     #[arg(short, action = clap::ArgAction::Count)]
@@ -51,6 +76,9 @@ pub enum Commands {
     /// List available contexts
     Contexts(commands::Contexts),
 
+    /// Check manifests for problems, without applying them
+    Validate(commands::Validate),
+
     /// Auto generate completions
     ///
     /// for examples:
@@ -66,31 +94,47 @@ impl Default for Commands {
     }
 }
 
-/// Parse a single key-value pair
-fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
-where
-    T: std::str::FromStr,
-    T::Err: Error + Send + Sync + 'static,
-    U: std::str::FromStr,
-    U::Err: Error + Send + Sync + 'static,
-{
-    let pos = s
-        .find('=')
-        .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{s}`"))?;
-    Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
-}
-
 pub(crate) fn load_config(args: &GlobalArgs) -> Result<Config> {
-    match lib_config(&args) {
+    let mut config = match lib_config(&args) {
         Ok(config) => match args.manifest_directory.clone() {
-            Some(manifest_path) => Ok(Config {
+            Some(manifest_path) => Config {
                 manifest_paths: vec![manifest_path],
                 ..config
-            }),
-            None => Ok(Config { ..config }),
+            },
+            None => Config { ..config },
         },
-        Err(error) => Err(error),
-    }
+        Err(error) => return Err(error),
+    };
+
+    // Resolved once, as a remote manifest path is cloned, and only once `-d`
+    // has replaced the manifest paths of the config
+    config.manifest_root = config
+        .manifest_paths
+        .first()
+        .and_then(|manifest_path| manifests::locate(manifest_path));
+
+    Ok(config)
+}
+
+/// Selects the profile requested on the command line, or the one chosen
+/// by the `default_profile` expression
+pub(crate) fn select_profile(args: &GlobalArgs, mut config: Config) -> Result<Config> {
+    let profile = match (&args.profile, &config.default_profile) {
+        (Some(profile), _) => profile.clone(),
+        (None, Some(expression)) => {
+            let contexts = build_contexts_for(&config, std::slice::from_ref(expression));
+            let mut scope = to_rhai(&contexts);
+
+            rhai_functions::eval::<String>(&mut scope, expression)
+                .map_err(|err| anyhow!("'default_profile' expression failed: {}", err))?
+        }
+        (None, None) => return Ok(config),
+    };
+
+    trace!(profile = profile.as_str(), message = "Selected profile");
+    config.select_profile(&profile)?;
+
+    Ok(config)
 }
 
 /// Check the current working directory for a `Comtrya.yaml` file
@@ -153,9 +197,18 @@ pub fn lib_config(args: &GlobalArgs) -> anyhow::Result<Config> {
         }
     };
 
-    let mut defines_iterator = args.defines.iter();
-    while let Some(pair) = defines_iterator.next() {
-        config.variables.insert(pair.0.clone(), pair.1.clone());
+    config.vars_files.extend(args.vars_file.iter().cloned());
+
+    // Files given on the command line have to be there, unlike `vars/`
+    for file in config.vars_files.iter() {
+        load_vars_file(file)?;
+    }
+
+    config.refresh_contexts = args.refresh_contexts;
+    config.os_override = args.os_override.clone();
+
+    for (key, value) in args.defines.iter() {
+        define(&mut config.defines, key, value.clone());
     }
 
     Ok(config)
@@ -229,7 +282,7 @@ fn find_configs(args: &GlobalArgs) -> Option<PathBuf> {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{lib_config, GlobalArgs};
+    use crate::config::{lib_config, load_config, GlobalArgs};
     use std::path::PathBuf;
 
     fn get_config_file() -> PathBuf {
@@ -267,4 +320,27 @@ mod tests {
         let result = lib_config(&args);
         assert!(!result.is_err());
     }
+
+    /// Test the manifest root follows `-d`, not the config's manifest paths
+    #[test]
+    fn load_config_manifest_directory() -> anyhow::Result<()> {
+        let manifests = tempfile::tempdir()?;
+
+        let args = GlobalArgs {
+            config_path: Some(get_config_file().to_string_lossy().to_string()),
+            manifest_directory: Some(manifests.path().to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let config = load_config(&args)?;
+        assert_eq!(
+            Some(manifests.path().canonicalize()?),
+            config
+                .manifest_root
+                .map(|root| root.canonicalize())
+                .transpose()?
+        );
+
+        Ok(())
+    }
 }
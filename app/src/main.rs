@@ -3,8 +3,8 @@ use crate::config::{Commands, GlobalArgs};
 
 use std::io;
 
-use comtrya_lib::contexts::build_contexts;
 use comtrya_lib::contexts::Contexts;
+use comtrya_lib::contexts::{build_contexts, build_contexts_for};
 use comtrya_lib::manifests;
 
 use clap::Parser;
@@ -30,6 +30,7 @@ pub(crate) fn execute(runtime: Runtime) -> anyhow::Result<()> {
         Commands::Status(apply) => apply.status(&runtime),
         Commands::Version(version) => version.execute(&runtime),
         Commands::Contexts(contexts) => contexts.execute(&runtime),
+        Commands::Validate(validate) => validate.execute(&runtime),
         Commands::GenCompletions(gen_completions) => gen_completions.execute(&runtime),
     }
 }
@@ -63,20 +64,32 @@ fn main() -> anyhow::Result<()> {
     let args = GlobalArgs::parse();
     configure_tracing(&args);
 
-    let config = match config::load_config(&args) {
-        Ok(config) => config,
-        Err(error) => {
-            error!("{}", error.to_string());
-            panic!();
-        }
-    };
+    let config =
+        match config::load_config(&args).and_then(|config| config::select_profile(&args, config)) {
+            Ok(config) => config,
+            Err(error) => {
+                error!("{}", error.to_string());
+                panic!();
+            }
+        };
 
     if !config.disable_update_check {
         check_for_updates(args.no_color);
     }
 
-    // Run Context Providers
-    let contexts = build_contexts(&config);
+    // Run Context Providers, the slow ones only when the manifests reference them
+    let contexts = match &args.command {
+        Commands::Apply(_) | Commands::Status(_) | Commands::Validate(_) => {
+            let sources = config
+                .manifest_root
+                .as_deref()
+                .map(manifests::sources)
+                .unwrap_or_default();
+
+            build_contexts_for(&config, &sources)
+        }
+        _ => build_contexts(&config),
+    };
     let runtime = Runtime {
         args,
         config,
@@ -60,3 +60,105 @@ actions:
 
     assert.success();
 }
+
+#[test]
+fn registered_outputs_survive_a_rerun() {
+    let t = TempDir::new().expect("could not create tempdir");
+    let path = t.path().to_path_buf();
+    dir(
+        "directory",
+        vec![
+            f(
+                "toolchain.yaml",
+                r#"
+actions:
+  - action: directory.create
+    path: "{{ manifest.root_dir }}/created"
+    register: created
+
+outputs:
+  created: "[{{ registered.created }}]"
+"#,
+            ),
+            f(
+                "tools.yaml",
+                r#"
+depends:
+  - toolchain
+
+actions:
+  - action: command.run
+    command: touch
+    args:
+      - "{{ manifest.root_dir }}/ran{{ deps.toolchain.created }}"
+"#,
+            ),
+        ],
+    )
+    .create_in(&path)
+    .expect("should have create test directories");
+
+    let ran = path.join("directory").join("ran[]");
+
+    cd(path.clone())
+        .run("--no-color -d ./directory apply")
+        .success();
+    assert!(ran.exists());
+
+    // The second time, there's nothing to do for the directory
+    std::fs::remove_file(&ran).expect("should have removed the file");
+    cd(path).run("--no-color -d ./directory apply").success();
+    assert!(ran.exists());
+}
+
+#[test]
+fn manifest_directory_has_the_variables() {
+    let t = TempDir::new().expect("could not create tempdir");
+    let path = t.path().to_path_buf();
+    dir(
+        "directory",
+        vec![dir("vars", vec![f("all.yaml", "x: fromvars\n")])],
+    )
+    .create_in(&path)
+    .expect("should have create test directories");
+
+    cd(path)
+        .run("--no-color -d ./directory contexts get variables.x")
+        .success()
+        .stdout(predicates::str::contains("fromvars"));
+}
+
+#[test]
+fn unresolved_dependencies_only_skip_their_manifest() {
+    let t = TempDir::new().expect("could not create tempdir");
+    let path = t.path().to_path_buf();
+    dir(
+        "directory",
+        vec![
+            f(
+                "broken.yaml",
+                r#"
+depends:
+  - missing
+
+actions: []
+"#,
+            ),
+            f(
+                "fine.yaml",
+                r#"
+actions:
+  - action: directory.create
+    path: "{{ manifest.root_dir }}/created"
+"#,
+            ),
+        ],
+    )
+    .create_in(&path)
+    .expect("should have create test directories");
+
+    cd(path.clone())
+        .run("--no-color -d ./directory apply -m fine")
+        .success();
+    assert!(path.join("directory").join("created").exists());
+}
@@ -1,20 +1,20 @@
 use crate::actions::Action;
 use crate::atoms::file::Chmod;
 use crate::atoms::http::Download;
-use crate::contexts::Contexts;
+use crate::contexts::{get, Contexts};
 use crate::manifests::Manifest;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
 use tokio::runtime::Runtime;
 use tracing::debug;
 
 #[derive(Clone, Debug, Default, JsonSchema, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BinaryGitHub {
     pub name: String,
-    pub directory: String,
+    pub directory: CustomPathBuf,
     pub repository: String,
     pub version: Option<String>,
 }
@@ -32,9 +32,11 @@ impl Action for BinaryGitHub {
         )
     }
 
-    fn plan(&self, _: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
+    fn plan(&self, manifest: &Manifest, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
+        let path = self.directory.resolve(manifest)?.join(&self.name);
+
         // Don't need to do anything if something already exists at the path
-        if std::path::Path::new(format!("{}/{}", self.directory, self.name).as_str()).exists() {
+        if path.exists() {
             return Ok(vec![]);
         };
 
@@ -70,35 +72,11 @@ impl Action for BinaryGitHub {
             }
         };
 
+        let score_terms = score_terms(contexts);
+
         let asset: Option<GitHubAsset> = release.assets.into_iter().fold(None, |acc, asset| {
             let mut score = 0;
 
-            let mut score_terms = vec![
-                std::env::consts::OS.to_lowercase(),
-                std::env::consts::ARCH.to_lowercase(),
-            ];
-
-            let os = os_info::get();
-            if os.os_type() == os_info::Type::Macos {
-                score_terms.push(String::from("darwin"));
-                score_terms.push(String::from("apple"));
-            } else {
-                score_terms.push(os.os_type().to_string());
-            };
-
-            if std::env::consts::ARCH == "aarch64" {
-                score_terms.push("arm".to_string());
-                score_terms.push("aarch".to_string());
-            } else {
-                score_terms.push("unknown".to_string());
-            };
-
-            match os.bitness() {
-                os_info::Bitness::X32 => score_terms.push("32".to_string()),
-                os_info::Bitness::X64 => score_terms.push("64".to_string()),
-                _ => (),
-            }
-
             score_terms.iter().for_each(|term| {
                 if asset.name.to_lowercase().contains(term.as_str()) {
                     score += 1;
@@ -137,19 +115,84 @@ impl Action for BinaryGitHub {
             Step {
                 atom: Box::new(Download {
                     url: asset.url,
-                    to: PathBuf::from(format!("{}/{}", self.directory, self.name)),
+                    to: path.clone(),
                 }),
                 initializers: vec![],
                 finalizers: vec![],
             },
             Step {
-                atom: Box::new(Chmod {
-                    path: PathBuf::from(format!("{}/{}", self.directory, self.name)),
-                    mode: 0o755,
-                }),
+                atom: Box::new(Chmod { path, mode: 0o755 }),
                 initializers: vec![],
                 finalizers: vec![],
             },
         ])
     }
 }
+
+/// Words we expect in the name of an asset built for this OS and architecture
+fn score_terms(contexts: &Contexts) -> Vec<String> {
+    let context = |key: &str, default: &str| {
+        get(contexts, key)
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| default.to_string())
+            .to_lowercase()
+    };
+
+    let os = context("os.name", std::env::consts::OS);
+    let arch = context("hardware.arch", std::env::consts::ARCH);
+    let distribution = context("os.distribution", "");
+
+    let mut score_terms = vec![os.clone(), arch.clone()];
+
+    if os == "macos" {
+        score_terms.push(String::from("darwin"));
+        score_terms.push(String::from("apple"));
+    } else {
+        score_terms.push(distribution);
+    };
+
+    if arch == "aarch64" {
+        score_terms.push("arm".to_string());
+        score_terms.push("aarch".to_string());
+    } else {
+        score_terms.push("unknown".to_string());
+    };
+
+    match context("os.bitness", "").as_str() {
+        "32-bit" => score_terms.push("32".to_string()),
+        "64-bit" => score_terms.push("64".to_string()),
+        _ => (),
+    }
+
+    score_terms
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn it_scores_assets_for_the_contexts_os() {
+        let mut contexts: Contexts = BTreeMap::new();
+        contexts.insert(
+            String::from("os"),
+            BTreeMap::from([
+                (String::from("name"), Value::from("macos")),
+                (String::from("distribution"), Value::from("Mac OS")),
+                (String::from("bitness"), Value::from("64-bit")),
+            ]),
+        );
+        contexts.insert(
+            String::from("hardware"),
+            BTreeMap::from([(String::from("arch"), Value::from("aarch64"))]),
+        );
+
+        assert_eq!(
+            vec!["macos", "aarch64", "darwin", "apple", "arm", "aarch", "64"],
+            score_terms(&contexts)
+        );
+    }
+}
@@ -2,6 +2,7 @@ use crate::contexts::Contexts;
 use crate::steps::finalizers::RemoveEnvVars;
 use crate::steps::initializers::SetEnvVars;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use crate::{actions::Action, manifests::Manifest, steps, utilities};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -18,7 +19,7 @@ pub struct RunCommand {
     pub privileged: bool,
 
     #[serde(default = "get_cwd")]
-    pub dir: String,
+    pub dir: CustomPathBuf,
 
     #[serde(default)]
     pub env: HashMap<String, String>,
@@ -28,9 +29,9 @@ fn get_false() -> bool {
     false
 }
 
-fn get_cwd() -> String {
+fn get_cwd() -> CustomPathBuf {
     std::env::current_dir()
-        .map(|current_dir| current_dir.display().to_string())
+        .map(|current_dir| current_dir.display().to_string().into())
         .expect("Failed to get current directory")
 }
 
@@ -39,7 +40,7 @@ impl Action for RunCommand {
         format!("Running {} command", self.command)
     }
 
-    fn plan(&self, _: &Manifest, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
+    fn plan(&self, manifest: &Manifest, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         use crate::atoms::command::Exec;
 
         let privilege_provider =
@@ -50,7 +51,7 @@ impl Action for RunCommand {
                 command: self.command.clone(),
                 arguments: self.args.clone(),
                 privileged: self.privileged,
-                working_dir: Some(self.dir.clone()),
+                working_dir: Some(self.dir.resolve(manifest)?.display().to_string()),
                 privilege_provider: privilege_provider.clone(),
                 ..Default::default()
             }),
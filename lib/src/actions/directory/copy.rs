@@ -2,14 +2,15 @@ use super::DirectoryAction;
 use crate::actions::Action;
 use crate::contexts::Contexts;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use crate::{atoms::command::Exec, manifests::Manifest};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DirectoryCopy {
-    pub from: String,
-    pub to: String,
+    pub from: CustomPathBuf,
+    pub to: CustomPathBuf,
 }
 
 impl DirectoryCopy {}
@@ -23,12 +24,13 @@ impl Action for DirectoryCopy {
     }
 
     fn plan(&self, manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let from: String = self.resolve(manifest, &self.from).display().to_string();
+        let from: String = self.resolve(manifest, &self.from)?.display().to_string();
+        let to: String = self.to.resolve(manifest)?.display().to_string();
 
         Ok(vec![Step {
             atom: Box::new(Exec {
                 command: String::from("Xcopy"),
-                arguments: vec!["/E".to_string(), "/I".to_string(), from, self.to.clone()],
+                arguments: vec!["/E".to_string(), "/I".to_string(), from, to],
                 ..Default::default()
             }),
             initializers: vec![],
@@ -44,9 +46,10 @@ impl Action for DirectoryCopy {
     }
 
     fn plan(&self, manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let mut from: String = self.resolve(manifest, &self.from).display().to_string();
+        let mut from: String = self.resolve(manifest, &self.from)?.display().to_string();
+        let to: String = self.to.resolve(manifest)?.display().to_string();
 
-        if self.to.ends_with("/") {
+        if to.ends_with('/') {
             from += "/."
         }
 
@@ -54,7 +57,7 @@ impl Action for DirectoryCopy {
             Step {
                 atom: Box::new(Exec {
                     command: String::from("mkdir"),
-                    arguments: vec![String::from("-p"), self.to.clone()],
+                    arguments: vec![String::from("-p"), to.clone()],
                     ..Default::default()
                 }),
                 initializers: vec![],
@@ -63,7 +66,7 @@ impl Action for DirectoryCopy {
             Step {
                 atom: Box::new(Exec {
                     command: String::from("cp"),
-                    arguments: vec![String::from("-r"), from, self.to.clone()],
+                    arguments: vec![String::from("-r"), from, to],
                     ..Default::default()
                 }),
                 initializers: vec![],
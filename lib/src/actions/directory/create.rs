@@ -1,14 +1,14 @@
 use crate::atoms::directory::Create as DirectoryCreateAtom;
 use crate::manifests::Manifest;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use crate::{actions::Action, contexts::Contexts};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DirectoryCreate {
-    pub path: String,
+    pub path: CustomPathBuf,
 }
 
 impl Action for DirectoryCreate {
@@ -16,10 +16,10 @@ impl Action for DirectoryCreate {
         format!("Creating directory {}", self.path)
     }
 
-    fn plan(&self, _: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
+    fn plan(&self, manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
         Ok(vec![Step {
             atom: Box::new(DirectoryCreateAtom {
-                path: PathBuf::from(&self.path),
+                path: self.path.resolve(manifest)?,
             }),
             initializers: vec![],
             finalizers: vec![],
@@ -1,4 +1,5 @@
-use crate::{actions::Action, manifests::Manifest};
+use crate::{actions::Action, manifests::Manifest, utilities::CustomPathBuf};
+use anyhow::anyhow;
 use normpath::PathExt;
 use std::path::PathBuf;
 
@@ -10,12 +11,17 @@ pub use create::DirectoryCreate;
 pub use remove::DirectoryRemove;
 
 pub trait DirectoryAction: Action {
-    fn resolve(&self, manifest: &Manifest, path: &str) -> PathBuf {
-        manifest
-            .root_dir
-            .as_ref()
-            .and_then(|root_dir| root_dir.join("files").join(path).normalize().ok())
+    /// Where a directory the action reads from is, relative paths are in
+    /// the `files/` directory of the manifest
+    fn resolve(&self, manifest: &Manifest, path: &CustomPathBuf) -> anyhow::Result<PathBuf> {
+        let files_dir = manifest
+            .files_dir()
+            .ok_or_else(|| anyhow!("Failed because manifest has no root_dir"))?;
+        let resolved = path.resolve_in(manifest, &files_dir)?;
+
+        resolved
+            .normalize()
             .map(|path| path.as_path().to_path_buf())
-            .expect("Failed to resolve path")
+            .map_err(|e| anyhow!("Failed to resolve {}: {}", resolved.display(), e))
     }
 }
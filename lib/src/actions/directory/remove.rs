@@ -1,16 +1,14 @@
-use std::path::PathBuf;
-
 use crate::atoms::directory::Remove as RemoveDirAtom;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{actions::Action, steps::Step};
+use crate::{actions::Action, steps::Step, utilities::CustomPathBuf};
 
 use super::DirectoryAction;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DirectoryRemove {
-    pub target: String,
+    pub target: CustomPathBuf,
 }
 
 impl DirectoryRemove {}
@@ -24,10 +22,10 @@ impl Action for DirectoryRemove {
 
     fn plan(
         &self,
-        _manifest: &crate::manifests::Manifest,
+        manifest: &crate::manifests::Manifest,
         _context: &crate::contexts::Contexts,
     ) -> anyhow::Result<Vec<crate::steps::Step>> {
-        let path = PathBuf::from(&self.target);
+        let path = self.target.resolve(manifest)?;
 
         let steps = vec![Step {
             atom: Box::new(RemoveDirAtom { target: path }),
@@ -1,4 +1,4 @@
-use crate::{actions::Action, steps::Step};
+use crate::{actions::Action, steps::Step, utilities::CustomPathBuf};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -7,7 +7,7 @@ use crate::atoms::file::Chown;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileChown {
-    pub path: String,
+    pub path: CustomPathBuf,
     pub user: Option<String>,
     pub group: Option<String>,
 }
@@ -34,12 +34,12 @@ impl Action for FileChown {
     #[cfg(unix)]
     fn plan(
         &self,
-        _: &crate::manifests::Manifest,
+        manifest: &crate::manifests::Manifest,
         _: &crate::contexts::Contexts,
     ) -> anyhow::Result<Vec<crate::steps::Step>> {
         let steps = vec![Step {
             atom: Box::new(Chown {
-                path: self.path.clone().parse()?,
+                path: self.path.resolve(manifest)?,
                 owner: self.user.clone().unwrap_or("".to_string()),
                 group: self.group.clone().unwrap_or("".to_string()),
             }),
@@ -3,22 +3,25 @@ use super::{default_chmod, from_octal};
 use crate::atoms::file::{Chown, Decrypt};
 use crate::manifests::Manifest;
 use crate::steps::Step;
-use crate::tera_functions::register_functions;
+use crate::tera_functions::render;
+use crate::utilities::CustomPathBuf;
 use crate::{actions::Action, contexts::to_tera};
 use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::error::Error as StdError;
-use std::{path::PathBuf, u32};
-use tera::Tera;
+use std::{
+    path::{Path, PathBuf},
+    u32,
+};
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileCopy {
     #[serde(alias = "source")]
-    pub from: String,
+    pub from: CustomPathBuf,
 
     #[serde(alias = "target")]
-    pub to: String,
+    pub to: CustomPathBuf,
 
     #[serde(default = "default_chmod", deserialize_with = "from_octal")]
     pub chmod: u32,
@@ -35,6 +38,16 @@ pub struct FileCopy {
     pub owner_group: Option<String>,
 }
 
+/// `file` in the template of a copied file
+#[derive(Serialize)]
+struct TemplateFile<'a> {
+    source: PathBuf,
+    target: &'a Path,
+
+    /// The contents of the target before it's replaced, if it exists
+    existing_contents: Option<String>,
+}
+
 fn default_template() -> bool {
     false
 }
@@ -53,15 +66,30 @@ impl Action for FileCopy {
         manifest: &Manifest,
         context: &crate::contexts::Contexts,
     ) -> anyhow::Result<Vec<Step>> {
+        let mut path = self.to.resolve(manifest)?;
+
+        if path.is_dir() {
+            if let Some(file_name) = self.from.file_name() {
+                path = path.join(file_name);
+            }
+        }
+
         let contents = match self.load(manifest, &self.from) {
             Ok(contents) => {
                 if self.template {
-                    let mut tera = Tera::default();
-                    register_functions(&mut tera);
-
                     let content_as_str = std::str::from_utf8(&contents)?;
 
-                    match tera.render_str(content_as_str, &to_tera(context)) {
+                    let mut context = to_tera(context);
+                    context.insert(
+                        "file",
+                        &TemplateFile {
+                            source: self.resolve(manifest, &self.from)?,
+                            target: &path,
+                            existing_contents: std::fs::read_to_string(&path).ok(),
+                        },
+                    );
+
+                    match render(manifest.root.as_deref(), content_as_str, &context) {
                         Ok(rendered) => rendered,
                         Err(err) => match err.source() {
                             Some(source) => {
@@ -95,14 +123,6 @@ impl Action for FileCopy {
         use crate::atoms::directory::Create as DirCreate;
         use crate::atoms::file::{Chmod, Create, SetContents};
 
-        let mut path = PathBuf::from(&self.to);
-
-        if path.is_dir() {
-            if let Some(file_name) = PathBuf::from(self.from.clone()).file_name() {
-                path = path.join(file_name);
-            }
-        }
-
         let parent = path.clone();
         let mut steps = vec![
             Step {
@@ -223,4 +243,49 @@ mod tests {
             }
         };
     }
+
+    #[test]
+    fn it_can_render_the_manifest_and_file_in_templates() -> anyhow::Result<()> {
+        use super::{CustomPathBuf, FileCopy};
+        use crate::{actions::Action, contexts::Contexts, manifests::Manifest};
+
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        std::fs::create_dir_all(root.join("files"))?;
+        std::fs::write(
+            root.join("files").join("gitconfig"),
+            "# Managed by comtrya manifest {{ manifest.name }} ({{ manifest.labels | join(sep=\",\") }})\n\
+             # From {{ file.source }} to {{ file.target }}\n\
+             {{ file.existing_contents | default(value=\"\") }}",
+        )?;
+        std::fs::write(root.join("gitconfig"), "[user]")?;
+
+        let manifest = Manifest {
+            name: Some(String::from("dev.git")),
+            labels: vec![String::from("dev"), String::from("git")],
+            root_dir: Some(root.clone()),
+            ..Default::default()
+        };
+
+        let copy = FileCopy {
+            from: CustomPathBuf::from("gitconfig"),
+            to: CustomPathBuf::from("@manifest"),
+            template: true,
+            ..Default::default()
+        };
+
+        for mut step in copy.plan(&manifest, &manifest.contexts(&Contexts::new()))? {
+            step.atom.execute()?;
+        }
+
+        assert_eq!(
+            format!(
+                "# Managed by comtrya manifest dev.git (dev,git)\n# From {0}/files/gitconfig to {0}/gitconfig\n[user]",
+                root.display()
+            ),
+            std::fs::read_to_string(root.join("gitconfig"))?
+        );
+
+        Ok(())
+    }
 }
@@ -3,16 +3,17 @@ use super::{default_chmod, from_octal};
 use crate::atoms::file::Chown;
 use crate::manifests::Manifest;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use crate::{actions::Action, contexts::Contexts};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, u32};
+use std::u32;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename = "file.download")]
 pub struct FileDownload {
     pub from: String,
-    pub to: String,
+    pub to: CustomPathBuf,
 
     #[serde(default = "default_chmod", deserialize_with = "from_octal")]
     pub chmod: u32,
@@ -40,12 +41,12 @@ impl Action for FileDownload {
         format!("Downloading file {} to {}", self.from, self.to)
     }
 
-    fn plan(&self, _manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
+    fn plan(&self, manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
         use crate::atoms::directory::Create as DirCreate;
         use crate::atoms::file::Chmod;
         use crate::atoms::http::Download;
 
-        let path = PathBuf::from(&self.to);
+        let path = self.to.resolve(manifest)?;
         let parent = path.clone();
 
         let mut steps = vec![
@@ -158,7 +159,7 @@ mod tests {
     fn contains_chown_step() {
         let file_download = FileDownload {
             from: "test".to_string(),
-            to: "abc".to_string(),
+            to: "abc".into(),
             chmod: 1,
             template: false,
             owner_user: Some("test".to_string()),
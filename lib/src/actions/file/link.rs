@@ -3,6 +3,7 @@ use crate::manifests::Manifest;
 use crate::steps::initializers::FileExists;
 use crate::steps::initializers::FlowControl::Ensure;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use crate::{actions::Action, contexts::Contexts};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -13,11 +14,11 @@ use tracing::error;
 // TODO: Next Major Version - Deprecate from and to
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileLink {
-    pub from: Option<String>,
-    pub source: Option<String>,
+    pub from: Option<CustomPathBuf>,
+    pub source: Option<CustomPathBuf>,
 
-    pub target: Option<String>,
-    pub to: Option<String>,
+    pub target: Option<CustomPathBuf>,
+    pub to: Option<CustomPathBuf>,
 
     #[serde(default = "walk_dir_default")]
     pub walk_dir: bool,
@@ -28,25 +29,25 @@ fn walk_dir_default() -> bool {
 }
 
 impl FileLink {
-    fn source(&self) -> String {
+    fn source(&self) -> CustomPathBuf {
         if self.source.is_none() && self.from.is_none() {
             error!("Field 'source' is required for file.link");
         }
 
         if let Some(ref source) = self.source {
-            source.to_string()
+            source.clone()
         } else {
             // .unwrap() is safe here because we already checked for None
             self.from.clone().unwrap()
         }
     }
 
-    fn target(&self) -> String {
+    fn target(&self) -> CustomPathBuf {
         if self.target.is_none() && self.to.is_none() {
             error!("Field 'target' is required for file.link");
         }
         if let Some(ref target) = self.target {
-            target.to_string()
+            target.clone()
         } else {
             // .unwrap() is safe here because we already checked for None
             self.to.clone().unwrap()
@@ -120,15 +121,19 @@ impl Action for FileLink {
     fn summarize(&self) -> String {
         format!(
             "Linking file {} to {}",
-            self.from.clone().unwrap_or(String::from("unknown")),
-            self.to.clone().unwrap_or(String::from("unknown"))
+            self.from
+                .as_ref()
+                .map_or(String::from("unknown"), CustomPathBuf::to_string),
+            self.to
+                .as_ref()
+                .map_or(String::from("unknown"), CustomPathBuf::to_string)
         )
     }
 
     fn plan(&self, manifest: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let from: PathBuf = self.resolve(manifest, self.source().as_str())?;
+        let from: PathBuf = self.resolve(manifest, &self.source())?;
 
-        let to = PathBuf::from(self.target());
+        let to = self.target().resolve(manifest)?;
 
         // Can't walk a file
         if from.is_file() {
@@ -209,7 +214,6 @@ mod tests {
             actions: vec![],
             depends: vec![],
             name: None,
-            dag_index: None,
             ..Default::default()
         };
 
@@ -226,8 +230,8 @@ mod tests {
             .to_string();
 
         let file_link_action: FileLink = FileLink {
-            source: Some(source_dir.path().to_str().unwrap().to_string()),
-            target: Some(target),
+            source: Some(source_dir.path().to_str().unwrap().into()),
+            target: Some(target.into()),
             ..Default::default()
         };
 
@@ -265,7 +269,6 @@ mod tests {
             actions: vec![],
             depends: vec![],
             name: None,
-            dag_index: None,
             ..Default::default()
         };
 
@@ -276,8 +279,8 @@ mod tests {
         let target: String = source_dir.parent().unwrap().to_str().unwrap().to_string();
 
         let file_link_action: FileLink = FileLink {
-            source: Some(source_dir.to_str().unwrap().to_string()),
-            target: Some(target),
+            source: Some(source_dir.to_str().unwrap().into()),
+            target: Some(target.into()),
             walk_dir: true,
             ..Default::default()
         };
@@ -7,24 +7,27 @@ pub mod unarchive;
 
 use crate::actions::Action;
 use crate::manifests::Manifest;
+use crate::utilities::CustomPathBuf;
 use anyhow::{anyhow, Result};
 use normpath::PathExt;
 use serde::{de::Error, Deserialize, Deserializer};
 use std::path::PathBuf;
 
 pub trait FileAction: Action {
-    fn resolve(&self, manifest: &Manifest, path: &str) -> anyhow::Result<PathBuf> {
-        Ok(manifest
-            .root_dir
-            .clone()
-            .ok_or_else(|| anyhow!("Failed because manifest has no root_dir"))?
-            .join("files")
-            .join(path)
+    /// Where a file the action reads from is, relative paths are in the
+    /// `files/` directory of the manifest
+    fn resolve(&self, manifest: &Manifest, path: &CustomPathBuf) -> anyhow::Result<PathBuf> {
+        let files_dir = manifest
+            .files_dir()
+            .ok_or_else(|| anyhow!("Failed because manifest has no root_dir"))?;
+        let resolved = path.resolve_in(manifest, &files_dir)?;
+
+        Ok(resolved
             .normalize()
             .map_err(|e| {
                 anyhow!(
                     "Resolution of {} failed in manifest {} because {}",
-                    path.to_string(),
+                    resolved.display(),
                     manifest
                         .name
                         .as_ref()
@@ -36,14 +39,12 @@ pub trait FileAction: Action {
             .to_path_buf())
     }
 
-    fn load(&self, manifest: &Manifest, path: &str) -> Result<Vec<u8>> {
+    fn load(&self, manifest: &Manifest, path: &CustomPathBuf) -> Result<Vec<u8>> {
         use std::io::ErrorKind;
-        let file_path = manifest
-            .root_dir
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Cannot extract root dir"))?
-            .join("files")
-            .join(path);
+        let files_dir = manifest
+            .files_dir()
+            .ok_or_else(|| anyhow::anyhow!("Cannot extract root dir"))?;
+        let file_path = path.resolve_in(manifest, &files_dir)?;
 
         std::fs::read(file_path.clone()).map_err(|e| match e.kind() {
             ErrorKind::NotFound => anyhow!(
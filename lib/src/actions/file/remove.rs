@@ -1,15 +1,13 @@
-use std::path::PathBuf;
-
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{actions::Action, steps::Step};
+use crate::{actions::Action, steps::Step, utilities::CustomPathBuf};
 
 use super::FileAction;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileRemove {
-    pub target: String,
+    pub target: CustomPathBuf,
 }
 
 impl FileRemove {}
@@ -23,12 +21,12 @@ impl Action for FileRemove {
 
     fn plan(
         &self,
-        _: &crate::manifests::Manifest,
+        manifest: &crate::manifests::Manifest,
         _: &crate::contexts::Contexts,
     ) -> anyhow::Result<Vec<crate::steps::Step>> {
         use crate::atoms::file::Remove as RemoveFile;
 
-        let path = PathBuf::from(&self.target);
+        let path = self.target.resolve(manifest)?;
 
         let steps = vec![Step {
             atom: Box::new(RemoveFile { target: path }),
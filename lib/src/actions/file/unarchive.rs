@@ -2,6 +2,7 @@ use super::FileAction;
 use crate::atoms::file::Unarchive;
 use crate::manifests::Manifest;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use crate::{actions::Action, contexts::Contexts};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -9,10 +10,10 @@ use serde::{Deserialize, Serialize};
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileUnarchive {
     #[serde(alias = "source")]
-    pub from: String,
+    pub from: CustomPathBuf,
 
     #[serde(alias = "target")]
-    pub to: String,
+    pub to: CustomPathBuf,
 
     pub force: Option<bool>,
 }
@@ -26,11 +27,11 @@ impl Action for FileUnarchive {
         format!("Unarchiving file {} to {}", self.from, self.to)
     }
 
-    fn plan(&self, _manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
+    fn plan(&self, manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
         let steps = vec![Step {
             atom: Box::new(Unarchive {
-                origin: self.from.clone().into(),
-                dest: self.to.clone().into(),
+                origin: self.from.resolve(manifest)?,
+                dest: self.to.resolve(manifest)?,
                 force: self.force.unwrap_or(true),
             }),
             initializers: vec![],
@@ -2,14 +2,14 @@ use crate::actions::Action;
 use crate::contexts::Contexts;
 use crate::manifests::Manifest;
 use crate::steps::Step;
+use crate::utilities::CustomPathBuf;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GitClone {
     pub repo_url: String,
-    pub directory: String,
+    pub directory: CustomPathBuf,
 }
 
 impl Action for GitClone {
@@ -17,12 +17,12 @@ impl Action for GitClone {
         format!("Cloning repository {} to {}", self.repo_url, self.directory)
     }
 
-    fn plan(&self, _: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
+    fn plan(&self, manifest: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
         let url = gix::url::parse(self.repo_url.as_str().into())?;
         Ok(vec![Step {
             atom: Box::new(crate::atoms::git::Clone {
                 repository: url.clone(),
-                directory: PathBuf::from(self.directory.clone()),
+                directory: self.directory.resolve(manifest)?,
             }),
             initializers: vec![],
             finalizers: vec![],
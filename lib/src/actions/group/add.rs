@@ -1,3 +1,4 @@
+use super::providers::GroupProviders;
 use super::Group;
 use super::GroupVariant;
 use crate::actions::Action;
@@ -14,8 +15,8 @@ impl Action for GroupAdd {
     }
 
     fn plan(&self, _manifest: &Manifest, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let variant: GroupVariant = self.into();
-        let box_provider = variant.provider.clone().get_provider();
+        let variant = GroupVariant::resolve(self, contexts);
+        let box_provider = GroupProviders::resolve(&variant.provider, contexts).get_provider();
         let provider = box_provider.deref();
 
         let mut atoms: Vec<Step> = vec![];
@@ -1,39 +1,40 @@
 pub mod add;
 pub mod providers;
 
+use crate::actions::{variant_key::find_variant, VariantKey};
+use crate::contexts::Contexts;
+use indexmap::IndexMap;
 use providers::GroupProviders;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use tracing::debug;
 
 #[derive(JsonSchema, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Group {
+    /// Defaults to the group provider for the OS in the `os` context
     #[serde(default)]
-    provider: GroupProviders,
+    provider: Option<GroupProviders>,
 
     #[serde(default)]
     group_name: String,
 
     #[serde(default)]
-    variants: HashMap<os_info::Type, GroupVariant>,
+    variants: IndexMap<VariantKey, GroupVariant>,
 }
 
 #[derive(JsonSchema, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct GroupVariant {
     #[serde(default)]
-    provider: GroupProviders,
+    provider: Option<GroupProviders>,
 
     #[serde(default)]
     group_name: String,
 }
 
-impl From<&Group> for GroupVariant {
-    fn from(group: &Group) -> Self {
-        let os = os_info::get();
-
-        // Check for variant configuration for this OS
-        let variant = group.variants.get(&os.os_type());
+impl GroupVariant {
+    /// The group, overlaid with the first variant matching the contexts
+    fn resolve(group: &Group, contexts: &Contexts) -> Self {
+        let variant = find_variant(&group.variants, contexts);
 
         // No variant overlays
         if variant.is_none() {
@@ -4,7 +4,7 @@ use crate::steps::Step;
 mod none;
 use self::{freebsd::FreeBSDGroupProvider, none::NoneGroupProvider};
 use super::GroupVariant;
-use crate::contexts::Contexts;
+use crate::contexts::{get, Contexts};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -40,20 +40,20 @@ impl GroupProviders {
     }
 }
 
-#[allow(clippy::derivable_impls)]
-impl Default for GroupProviders {
-    #[cfg(target_os = "linux")]
-    fn default() -> Self {
-        GroupProviders::Linux
-    }
-
-    #[cfg(not(target_os = "linux"))]
-    fn default() -> Self {
-        let info = os_info::get();
+impl GroupProviders {
+    /// The provider a manifest asked for, or the one for the OS in `os.name`
+    pub fn resolve(provider: &Option<Self>, contexts: &Contexts) -> Self {
+        if let Some(provider) = provider {
+            return provider.clone();
+        }
 
-        match info.os_type() {
-            os_info::Type::FreeBSD => GroupProviders::FreeBSD,
-            os_info::Type::Macos => GroupProviders::MacOs,
+        match get(contexts, "os.name")
+            .map(|name| name.to_string())
+            .as_deref()
+        {
+            Some("linux") => GroupProviders::Linux,
+            Some("freebsd") => GroupProviders::FreeBSD,
+            Some("macos") => GroupProviders::MacOs,
             _ => GroupProviders::None,
         }
     }
@@ -5,12 +5,15 @@ mod file;
 mod git;
 mod group;
 mod macos;
-mod package;
+pub(crate) mod package;
 mod plugin;
 mod user;
+mod variant_key;
 
 use crate::actions::macos::MacOSDefault;
-use crate::{contexts::Contexts, manifests::Manifest, steps::Step};
+use crate::manifests::with_values;
+use crate::utilities::{struct_fields, CustomPathBuf};
+use crate::{contexts::Contexts, manifests::Manifest, rhai_functions, steps::Step};
 use anyhow::anyhow;
 use binary::BinaryGitHub;
 use command::run::RunCommand;
@@ -25,17 +28,32 @@ use git::GitClone;
 use group::add::GroupAdd;
 use package::{PackageInstall, PackageRepository};
 use plugin::Plugin;
-use rhai::Engine;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
 use std::fmt::Display;
 use std::ops::Deref;
+use std::path::PathBuf;
 use tracing::{error, warn};
 use user::add::UserAdd;
 
 use self::user::add_group::UserAddGroup;
-
-#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub use variant_key::VariantKey;
+
+/// The fields every action may be written with, besides its own
+pub const ACTION_FIELDS: &[&str] = &[
+    "action",
+    "where",
+    "variants",
+    "variants_merge",
+    "variants_all",
+    "register",
+];
+
+/// The fields every variant may be written with, besides the action's own
+pub const VARIANT_FIELDS: &[&str] = &["where", "merge"];
+
+#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Serialize, Default)]
 pub struct ConditionalVariantAction<T> {
     #[serde(flatten)]
     pub action: T,
@@ -45,43 +63,195 @@ pub struct ConditionalVariantAction<T> {
 
     #[serde(default)]
     pub variants: Vec<Variant<T>>,
+
+    /// Deep merge every variant onto this action, rather than replacing it
+    #[serde(default)]
+    pub variants_merge: bool,
+
+    /// Use every matching variant in order, rather than only the first one
+    #[serde(default)]
+    pub variants_all: bool,
+
+    /// Keep the output of the action as `registered.<name>`, for the
+    /// manifest's `outputs`
+    #[serde(default)]
+    pub register: Option<String>,
+
+    /// The fields of the action as written, variants are merged onto these
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub fields: JsonValue,
+
+    /// The role the action comes from, if any
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub role: Option<ActionRole>,
+}
+
+/// Where a role's action comes from. Its files are the role's, and the
+/// role's inputs are available to it as `inputs.<name>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionRole {
+    pub root_dir: PathBuf,
+
+    #[serde(default)]
+    pub inputs: JsonMap<String, JsonValue>,
 }
 
-#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// The field roles mark their actions with, see `ActionRole`
+pub(crate) const ROLE_FIELD: &str = "$role";
+
+#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct Variant<T> {
     #[serde(flatten)]
     pub action: T,
 
     #[serde(rename = "where")]
     pub condition: Option<String>,
+
+    /// Deep merge this variant onto the action, rather than replacing it
+    #[serde(default)]
+    pub merge: bool,
+
+    /// The fields of the variant as written
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub fields: JsonValue,
 }
 
 impl<T> Variant<T> {
     pub fn new(action: T, condition: Option<String>) -> Self {
-        Self { action, condition }
+        Self {
+            action,
+            condition,
+            merge: false,
+            fields: JsonValue::Null,
+        }
     }
 }
 
-impl<T> Action for ConditionalVariantAction<T>
+/// Variants may only give the fields they change when merging, so the
+/// action is kept as written and `T` is deserialized after merging
+impl<'de, T> Deserialize<'de> for ConditionalVariantAction<T>
 where
-    T: Action,
+    T: DeserializeOwned,
 {
-    fn summarize(&self) -> String {
-        self.action.summarize()
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawAction {
+            #[serde(rename = "where")]
+            condition: Option<String>,
+
+            #[serde(default)]
+            variants: Vec<RawVariant>,
+
+            #[serde(default)]
+            variants_merge: bool,
+
+            #[serde(default)]
+            variants_all: bool,
+
+            #[serde(default)]
+            register: Option<String>,
+
+            #[serde(default, rename = "$role")]
+            role: Option<ActionRole>,
+
+            #[serde(flatten)]
+            fields: JsonMap<String, JsonValue>,
+        }
+
+        #[derive(Deserialize)]
+        struct RawVariant {
+            #[serde(rename = "where")]
+            condition: Option<String>,
+
+            #[serde(default)]
+            merge: bool,
+
+            #[serde(flatten)]
+            fields: JsonMap<String, JsonValue>,
+        }
+
+        let raw = RawAction::deserialize(deserializer)?;
+        let fields = JsonValue::Object(raw.fields);
+
+        let variants = raw
+            .variants
+            .into_iter()
+            .map(|variant| {
+                let merge = raw.variants_merge || variant.merge;
+                let variant_fields = JsonValue::Object(variant.fields);
+
+                let action = if merge {
+                    let mut merged = fields.clone();
+                    merge_fields(&mut merged, &variant_fields);
+                    merged
+                } else {
+                    variant_fields.clone()
+                };
+
+                Ok(Variant {
+                    action: T::deserialize(action).map_err(D::Error::custom)?,
+                    condition: variant.condition,
+                    merge,
+                    fields: variant_fields,
+                })
+            })
+            .collect::<Result<Vec<Variant<T>>, D::Error>>()?;
+
+        Ok(Self {
+            action: T::deserialize(fields.clone()).map_err(D::Error::custom)?,
+            condition: raw.condition,
+            variants,
+            variants_merge: raw.variants_merge,
+            variants_all: raw.variants_all,
+            register: raw.register,
+            fields,
+            role: raw.role,
+        })
     }
+}
 
-    fn plan(&self, manifest: &Manifest, context: &Contexts) -> Result<Vec<Step>, anyhow::Error> {
-        let engine = Engine::new();
-        let mut scope = crate::contexts::to_rhai(context);
+/// Maps are merged key by key, anything else is replaced
+fn merge_fields(base: &mut JsonValue, overlay: &JsonValue) {
+    match (base, overlay) {
+        (JsonValue::Object(base), JsonValue::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_fields(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+impl<T> ConditionalVariantAction<T>
+where
+    T: Clone + DeserializeOwned,
+{
+    /// The action as written and the action of every variant
+    fn actions(&self) -> impl Iterator<Item = &T> {
+        std::iter::once(&self.action).chain(self.variants.iter().map(|variant| &variant.action))
+    }
 
-        let variant = self.variants.iter().find(|variant| {
-            if variant.condition.is_none() {
+    /// The action described by the matching variants, if any match. With
+    /// `variants_all`, every matching variant is applied in order, each one
+    /// merging onto or replacing the result of the ones before it.
+    fn variant_action(&self, scope: &mut rhai::Scope) -> anyhow::Result<Option<T>> {
+        let mut matching = self.variants.iter().filter(|variant| {
+            let Some(condition) = &variant.condition else {
                 return false;
-            }
+            };
 
-            // .unwrap() is safe here because we checked for None above
-            let condition = variant.condition.clone().unwrap();
-            match engine.eval_with_scope::<bool>(&mut scope, condition.as_str()) {
+            match rhai_functions::eval::<bool>(scope, condition.as_str()) {
                 Ok(b) => b,
                 Err(error) => {
                     error!("Failed execution condition for action: {}", error);
@@ -90,8 +260,73 @@ where
             }
         });
 
-        if let Some(variant) = variant {
-            return variant.action.plan(manifest, context);
+        if !self.variants_all {
+            return Ok(matching.next().map(|variant| variant.action.clone()));
+        }
+
+        let matching: Vec<&Variant<T>> = matching.collect();
+
+        match matching.as_slice() {
+            [] => Ok(None),
+            [variant] => Ok(Some(variant.action.clone())),
+            variants => {
+                let mut fields = self.fields.clone();
+
+                for variant in variants {
+                    if variant.merge {
+                        merge_fields(&mut fields, &variant.fields);
+                    } else {
+                        fields = variant.fields.clone();
+                    }
+                }
+
+                Ok(Some(T::deserialize(fields)?))
+            }
+        }
+    }
+}
+
+impl<T> Action for ConditionalVariantAction<T>
+where
+    T: Action + Clone + DeserializeOwned,
+{
+    fn summarize(&self) -> String {
+        self.action.summarize()
+    }
+
+    fn register(&self) -> Option<&str> {
+        self.register.as_deref()
+    }
+
+    fn plan(&self, manifest: &Manifest, context: &Contexts) -> Result<Vec<Step>, anyhow::Error> {
+        let Some(role) = &self.role else {
+            return self.plan_conditionally(manifest, context);
+        };
+
+        let manifest = Manifest {
+            root_dir: Some(role.root_dir.clone()),
+            root: Some(role.root_dir.clone()),
+            ..manifest.clone()
+        };
+        let context = with_values(context, "inputs", role.inputs.iter());
+
+        self.plan_conditionally(&manifest, &context)
+    }
+}
+
+impl<T> ConditionalVariantAction<T>
+where
+    T: Action + Clone + DeserializeOwned,
+{
+    fn plan_conditionally(
+        &self,
+        manifest: &Manifest,
+        context: &Contexts,
+    ) -> Result<Vec<Step>, anyhow::Error> {
+        let mut scope = crate::contexts::to_rhai(context);
+
+        if let Some(action) = self.variant_action(&mut scope)? {
+            return action.plan(manifest, context);
         }
 
         if self.condition.is_none() {
@@ -101,7 +336,7 @@ where
         // .unwrap() is safe here because we checked for None above
         let condition = self.condition.as_ref().unwrap();
 
-        match engine.eval_with_scope::<bool>(&mut scope, condition.as_str()) {
+        match rhai_functions::eval::<bool>(&mut scope, condition.as_str()) {
             Ok(true) => self.action.plan(manifest, context),
             Ok(false) => Ok(vec![]),
             Err(error) => Err(anyhow!("Failed execution condition for action: {}", error)),
@@ -199,6 +434,48 @@ impl Actions {
             Actions::Plugin(a) => a,
         }
     }
+
+    /// The fields of the action named `name` itself, aliases included. `None`
+    /// for unknown actions and those taking arbitrary fields.
+    pub fn fields(name: &str) -> Option<&'static [&'static str]> {
+        match name {
+            "command.run" | "cmd.run" => struct_fields::<RunCommand>(),
+            "directory.copy" | "dir.copy" => struct_fields::<DirectoryCopy>(),
+            "directory.create" | "dir.create" => struct_fields::<DirectoryCreate>(),
+            "directory.remove" | "dir.remove" => struct_fields::<DirectoryRemove>(),
+            "file.copy" => struct_fields::<FileCopy>(),
+            "file.chown" => struct_fields::<FileChown>(),
+            "file.download" => struct_fields::<FileDownload>(),
+            "file.link" => struct_fields::<FileLink>(),
+            "file.remove" => struct_fields::<FileRemove>(),
+            "file.unarchive" => struct_fields::<FileUnarchive>(),
+            "binary.github" | "binary.gh" | "bin.github" | "bin.gh" => {
+                struct_fields::<BinaryGitHub>()
+            }
+            "git.clone" => struct_fields::<GitClone>(),
+            "group.add" => struct_fields::<GroupAdd>(),
+            "macos.default" => struct_fields::<MacOSDefault>(),
+            "package.install" | "package.installed" => struct_fields::<PackageInstall>(),
+            "package.repository" | "package.repo" => struct_fields::<PackageRepository>(),
+            "user.add" => struct_fields::<UserAdd>(),
+            "user.group" => struct_fields::<UserAddGroup>(),
+            _ => None,
+        }
+    }
+
+    /// The paths, relative to the manifest's `files` directory, the action
+    /// and its variants read from
+    pub fn files(&self) -> Vec<&CustomPathBuf> {
+        match self {
+            Actions::FileCopy(a) => a.actions().map(|copy| &copy.from).collect(),
+            Actions::DirectoryCopy(a) => a.actions().map(|copy| &copy.from).collect(),
+            Actions::FileLink(a) => a
+                .actions()
+                .filter_map(|link| link.source.as_ref().or(link.from.as_ref()))
+                .collect(),
+            _ => vec![],
+        }
+    }
 }
 
 impl Deref for Actions {
@@ -282,11 +559,15 @@ pub trait Action {
         "not found action summarize".to_string()
     }
     fn plan(&self, manifest: &Manifest, context: &Contexts) -> anyhow::Result<Vec<Step>>;
+    /// The name the output of the action is registered as, if any
+    fn register(&self) -> Option<&str> {
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::actions::{command::run::RunCommand, Actions};
+    use crate::actions::{command::run::RunCommand, Actions, ConditionalVariantAction};
     use crate::manifests::Manifest;
 
     #[test]
@@ -320,7 +601,8 @@ actions:
                     .unwrap()
                     .into_os_string()
                     .into_string()
-                    .unwrap(),
+                    .unwrap()
+                    .into(),
                 ..Default::default()
             }
         );
@@ -329,4 +611,100 @@ actions:
         assert_eq!(variant.condition, Some(String::from("Debian")));
         assert_eq!(variant.action.command, "halt");
     }
+
+    fn command_run(content: &str) -> ConditionalVariantAction<RunCommand> {
+        let m: Manifest = serde_yml::from_str(content).unwrap();
+
+        match &m.actions[0] {
+            Actions::CommandRun(cr) => cr.clone(),
+            _ => panic!("did not get a command to run"),
+        }
+    }
+
+    #[test]
+    fn can_merge_variants() {
+        let action = command_run(
+            r#"
+actions:
+- action: command.run
+  command: echo
+  args:
+    - hi
+  env:
+    EDITOR: vi
+  variants:
+    - where: os.name == "macos"
+      merge: true
+      args:
+        - hi from macOS
+      env:
+        PAGER: less
+    - where: os.name == "linux"
+      command: halt
+"#,
+        );
+
+        let variant = &action.variants[0];
+        assert!(variant.merge);
+        assert_eq!(variant.action.command, "echo");
+        assert_eq!(variant.action.args, vec![String::from("hi from macOS")]);
+        assert_eq!(variant.action.env.len(), 2);
+
+        let variant = &action.variants[1];
+        assert!(!variant.merge);
+        assert_eq!(variant.action.command, "halt");
+        assert!(variant.action.args.is_empty());
+    }
+
+    #[test]
+    fn can_apply_all_matching_variants() -> anyhow::Result<()> {
+        let action = command_run(
+            r#"
+actions:
+- action: command.run
+  command: echo
+  args:
+    - hi
+  variants_merge: true
+  variants_all: true
+  variants:
+    - where: "true"
+      args:
+        - first
+    - where: "false"
+      command: halt
+    - where: "true"
+      privileged: true
+"#,
+        );
+
+        let resolved = action
+            .variant_action(&mut rhai::Scope::new())?
+            .expect("variants to match");
+
+        assert_eq!(resolved.command, "echo");
+        assert_eq!(resolved.args, vec![String::from("first")]);
+        assert!(resolved.privileged);
+
+        Ok(())
+    }
+
+    #[test]
+    fn every_action_lists_its_fields() {
+        let err = serde_json::from_value::<Actions>(serde_json::json!({ "action": "unknown" }))
+            .unwrap_err()
+            .to_string();
+
+        // unknown variant `unknown`, expected one of `command.run`, ...
+        for name in err.split('`').skip(3).step_by(2) {
+            assert!(
+                name == "plugin" || Actions::fields(name).is_some(),
+                "{} doesn't list its fields",
+                name
+            );
+        }
+
+        assert!(Actions::fields("file.copy")
+            .is_some_and(|fields| fields.contains(&"from") && fields.contains(&"source")));
+    }
 }
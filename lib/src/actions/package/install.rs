@@ -18,8 +18,9 @@ impl Action for PackageInstall {
     }
 
     fn plan(&self, _manifest: &Manifest, context: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let variant: PackageVariant = self.into();
-        let box_provider = variant.provider.clone().get_provider();
+        let variant = PackageVariant::resolve(self, context);
+        let package_provider = PackageProviders::resolve(&variant.provider, context)?;
+        let box_provider = package_provider.clone().get_provider();
         let provider = box_provider.deref();
 
         let span = span!(
@@ -41,7 +42,7 @@ impl Action for PackageInstall {
             }
 
             if variant.file {
-                match variant.provider {
+                match package_provider {
                     PackageProviders::BsdPkg => debug!("Will attempt to install from local file."),
                     PackageProviders::Aptitude => {
                         debug!("Will attempt to install from local file.")
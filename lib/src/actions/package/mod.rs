@@ -2,12 +2,14 @@ mod install;
 mod providers;
 mod repository;
 
+use crate::actions::{variant_key::find_variant, VariantKey};
+use crate::contexts::Contexts;
+use indexmap::IndexMap;
 pub(crate) use install::PackageInstall;
-use providers::PackageProviders;
+pub(crate) use providers::PackageProviders;
 pub(crate) use repository::PackageRepository;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use tracing::debug;
 
 #[derive(JsonSchema, Clone, Debug, Default, Serialize, Deserialize)]
@@ -18,8 +20,9 @@ pub struct Package {
     #[serde(default)]
     list: Vec<String>,
 
+    /// Defaults to the package manager of the OS in the `os` context
     #[serde(default)]
-    provider: PackageProviders,
+    provider: Option<PackageProviders>,
 
     #[serde(default)]
     repository: Option<String>,
@@ -31,7 +34,7 @@ pub struct Package {
     extra_args: Vec<String>,
 
     #[serde(default)]
-    variants: HashMap<os_info::Type, PackageVariant>,
+    variants: IndexMap<VariantKey, PackageVariant>,
 
     #[serde(default)]
     file: bool,
@@ -44,8 +47,9 @@ pub struct PackageVariant {
     #[serde(default)]
     list: Vec<String>,
 
+    /// Defaults to the package manager of the OS in the `os` context
     #[serde(default)]
-    provider: PackageProviders,
+    provider: Option<PackageProviders>,
 
     #[serde(default)]
     extra_args: Vec<String>,
@@ -63,12 +67,10 @@ impl PackageVariant {
     }
 }
 
-impl From<&Package> for PackageVariant {
-    fn from(package: &Package) -> Self {
-        let os = os_info::get();
-
-        // Check for variant configuration for this OS
-        let variant = package.variants.get(&os.os_type());
+impl PackageVariant {
+    /// The package, overlaid with the first variant matching the contexts
+    fn resolve(package: &Package, contexts: &Contexts) -> Self {
+        let variant = find_variant(&package.variants, contexts);
 
         // No variant overlays
         if variant.is_none() {
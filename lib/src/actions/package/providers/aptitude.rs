@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, name_then_version, PackageProvider};
 use crate::actions::package::{repository::PackageRepository, PackageVariant};
 use crate::atoms::command::Exec;
 use crate::contexts::Contexts;
@@ -141,6 +141,14 @@ impl PackageProvider for Aptitude {
         Ok(package.packages())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        installed_from_command(
+            "dpkg-query",
+            &["--show", "--showformat=${Package}\t${Version}\n"],
+            name_then_version,
+        )
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let privilege_provider =
             utilities::get_privilege_provider(&contexts).unwrap_or_else(|| "sudo".to_string());
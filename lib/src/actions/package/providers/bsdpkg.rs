@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, name_then_version, PackageProvider};
 use crate::actions::package::repository::PackageRepository;
 use crate::contexts::Contexts;
 use crate::steps::finalizers::FlowControl::StopIf;
@@ -70,6 +70,10 @@ impl PackageProvider for BsdPkg {
         Ok(package.packages())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        installed_from_command("pkg", &["query", "%n\t%v"], name_then_version)
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let privilege_provider =
             utilities::get_privilege_provider(&contexts).unwrap_or_else(|| "sudo".to_string());
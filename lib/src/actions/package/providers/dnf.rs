@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{rpm_installed, PackageProvider};
 
 use crate::actions::package::{repository::PackageRepository, PackageVariant};
 use crate::atoms::command::Exec;
@@ -120,6 +120,10 @@ impl PackageProvider for Dnf {
         Ok(package.packages())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        rpm_installed()
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let privilege_provider =
             utilities::get_privilege_provider(&contexts).unwrap_or_else(|| "sudo".to_string());
@@ -160,7 +164,7 @@ mod test {
         let steps = dnf.add_repository(
             &PackageRepository {
                 name: String::from("test"),
-                provider: PackageProviders::Dnf,
+                provider: Some(PackageProviders::Dnf),
                 ..Default::default()
             },
             &contexts,
@@ -180,7 +184,7 @@ mod test {
                     url: String::from("abc"),
                     ..Default::default()
                 }),
-                provider: PackageProviders::Dnf,
+                provider: Some(PackageProviders::Dnf),
             },
             &contexts,
         );
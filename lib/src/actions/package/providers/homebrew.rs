@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, PackageProvider};
 use crate::actions::package::repository::PackageRepository;
 use crate::contexts::Contexts;
 use crate::steps::Step;
@@ -79,6 +79,15 @@ impl PackageProvider for Homebrew {
             .collect())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        // Multiple versions of a formula can be installed, the last one is the newest
+        installed_from_command("brew", &["list", "--versions"], |line| {
+            let mut words = line.split_whitespace();
+
+            Some((words.next()?.to_string(), words.last()?.to_string()))
+        })
+    }
+
     fn install(&self, package: &PackageVariant, _contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         // Does not require privilege escalation
 
@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, PackageProvider};
 use crate::actions::package::repository::PackageRepository;
 use crate::contexts::Contexts;
 use crate::steps::Step;
@@ -48,6 +48,19 @@ impl PackageProvider for Macports {
         Ok(vec![])
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        // Only the active version of a port counts as installed
+        installed_from_command("port", &["-q", "installed"], |line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?;
+            let version = words.next()?.trim_start_matches('@');
+
+            words
+                .any(|word| word == "(active)")
+                .then(|| (name.to_string(), version.to_string()))
+        })
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let cli = match which("port") {
             Ok(c) => c,
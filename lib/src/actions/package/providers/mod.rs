@@ -24,11 +24,13 @@ use self::xbps::Xbps;
 mod zypper;
 use self::zypper::Zypper;
 use super::{repository::PackageRepository, PackageVariant};
-use crate::contexts::Contexts;
+use crate::contexts::{get, Contexts};
+use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::process::Command;
 
-#[derive(JsonSchema, Clone, Debug, Serialize, Deserialize)]
+#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PackageProviders {
     #[serde(rename = "aptitude", alias = "apt", alias = "apt-get")]
     Aptitude,
@@ -86,44 +88,70 @@ impl PackageProviders {
     }
 }
 
-impl Default for PackageProviders {
-    fn default() -> Self {
-        let info = os_info::get();
-
-        println!("Info: {:?}", info);
-
-        match info.os_type() {
-            // Arch Variants
-            os_info::Type::Arch=> PackageProviders::Yay,
-            os_info::Type::Artix => PackageProviders::Yay,
-            os_info::Type::CachyOS => PackageProviders::Yay,
-            os_info::Type::EndeavourOS => PackageProviders::Yay,
-            os_info::Type::Manjaro=> PackageProviders::Yay,
-            // BSD operating systems
-            os_info::Type::DragonFly=> PackageProviders::BsdPkg,
-            os_info::Type::FreeBSD=> PackageProviders::BsdPkg,
-            os_info::Type::NetBSD => PackageProviders::Pkgin,
-            // Debian / Ubuntu Variants
-            os_info::Type::Debian => PackageProviders::Aptitude,
-            os_info::Type::Mint => PackageProviders::Aptitude,
-            os_info::Type::Pop => PackageProviders::Aptitude,
-            os_info::Type::Ubuntu => PackageProviders::Aptitude,
-            // For some reason, the Rust image is showing as this and
-            // its Debian based?
-            os_info::Type::OracleLinux => PackageProviders::Aptitude,
-            // OpenSUSE and SUSE
-            os_info::Type::openSUSE => PackageProviders::Zypper,
-            os_info::Type::SUSE => PackageProviders::Zypper,
-            // Red-Hat Variants
-            os_info::Type::Fedora => PackageProviders::Dnf,
-            os_info::Type::Redhat => PackageProviders::Dnf,
-            os_info::Type::RedHatEnterprise => PackageProviders::Dnf,
-            os_info::Type::CentOS => PackageProviders::Dnf,
-            // Other
-            os_info::Type::Macos => PackageProviders::Homebrew,
-            os_info::Type::Windows => PackageProviders::Winget,
-
-            _ => panic!("Sorry, but we don't have a default provider for {} OS. Please be explicit when requesting a package installation with `provider: XYZ`.", info.os_type()),
+/// The provider we'd use for each OS when a manifest doesn't ask for one
+const DEFAULT_PROVIDERS: &[(os_info::Type, PackageProviders)] = &[
+    // Arch Variants
+    (os_info::Type::Arch, PackageProviders::Yay),
+    (os_info::Type::Artix, PackageProviders::Yay),
+    (os_info::Type::CachyOS, PackageProviders::Yay),
+    (os_info::Type::EndeavourOS, PackageProviders::Yay),
+    (os_info::Type::Manjaro, PackageProviders::Yay),
+    // BSD operating systems
+    (os_info::Type::DragonFly, PackageProviders::BsdPkg),
+    (os_info::Type::FreeBSD, PackageProviders::BsdPkg),
+    (os_info::Type::NetBSD, PackageProviders::Pkgin),
+    // Debian / Ubuntu Variants
+    (os_info::Type::Debian, PackageProviders::Aptitude),
+    (os_info::Type::Mint, PackageProviders::Aptitude),
+    (os_info::Type::Pop, PackageProviders::Aptitude),
+    (os_info::Type::Ubuntu, PackageProviders::Aptitude),
+    // For some reason, the Rust image is showing as this and
+    // its Debian based?
+    (os_info::Type::OracleLinux, PackageProviders::Aptitude),
+    // OpenSUSE and SUSE
+    (os_info::Type::openSUSE, PackageProviders::Zypper),
+    (os_info::Type::SUSE, PackageProviders::Zypper),
+    // Red-Hat Variants
+    (os_info::Type::Fedora, PackageProviders::Dnf),
+    (os_info::Type::Redhat, PackageProviders::Dnf),
+    (os_info::Type::RedHatEnterprise, PackageProviders::Dnf),
+    (os_info::Type::CentOS, PackageProviders::Dnf),
+    // Other
+    (os_info::Type::Macos, PackageProviders::Homebrew),
+    (os_info::Type::Windows, PackageProviders::Winget),
+];
+
+impl PackageProviders {
+    /// The default provider for an OS type
+    pub fn for_os(os_type: os_info::Type) -> Option<Self> {
+        DEFAULT_PROVIDERS
+            .iter()
+            .find(|(os, _)| *os == os_type)
+            .map(|(_, provider)| provider.clone())
+    }
+
+    /// The default provider for the OS in `os.distribution`
+    pub fn detect(contexts: &Contexts) -> Option<Self> {
+        let distribution = get(contexts, "os.distribution")?.to_string();
+
+        DEFAULT_PROVIDERS
+            .iter()
+            .find(|(os, _)| os.to_string() == distribution)
+            .map(|(_, provider)| provider.clone())
+    }
+
+    /// The provider a manifest asked for, or the default for this OS
+    pub fn resolve(provider: &Option<Self>, contexts: &Contexts) -> anyhow::Result<Self> {
+        match provider {
+            Some(provider) => Ok(provider.clone()),
+            None => PackageProviders::detect(contexts).ok_or_else(|| {
+                anyhow!(
+                    "Sorry, but we don't have a default provider for {} OS. Please be explicit when requesting a package installation with `provider: XYZ`.",
+                    get(contexts, "os.distribution")
+                        .map(|distribution| distribution.to_string())
+                        .unwrap_or_else(|| String::from("this"))
+                )
+            }),
         }
     }
 }
@@ -139,5 +167,110 @@ pub trait PackageProvider {
         contexts: &Contexts,
     ) -> anyhow::Result<Vec<Step>>;
     fn query(&self, package: &PackageVariant) -> anyhow::Result<Vec<String>>;
+    /// Every package the provider has installed, as `(name, version)`
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        Err(anyhow!("{} can't list installed packages", self.name()))
+    }
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>>;
 }
+
+/// Runs `command` and parses a `(name, version)` pair out of every line it prints
+pub(crate) fn installed_from_command(
+    command: &str,
+    args: &[&str],
+    parse: fn(&str) -> Option<(String, String)>,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let output = Command::new(command).args(args).output()?;
+
+    if !output.status.success() {
+        return Err(anyhow!("{} exited with {}", command, output.status));
+    }
+
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .filter_map(parse)
+        .collect())
+}
+
+/// Every package in the RPM database, for the providers installing RPMs
+pub(crate) fn rpm_installed() -> anyhow::Result<Vec<(String, String)>> {
+    installed_from_command(
+        "rpm",
+        &[
+            "--query",
+            "--all",
+            "--queryformat=%{NAME}\t%{VERSION}-%{RELEASE}\n",
+        ],
+        name_then_version,
+    )
+}
+
+/// Whitespace separated `name version`, as printed by `pacman -Q` or `snap list`
+pub(crate) fn name_then_version(line: &str) -> Option<(String, String)> {
+    let mut words = line.split_whitespace();
+
+    Some((words.next()?.to_string(), words.next()?.to_string()))
+}
+
+/// `name-version`, as printed by `pkg_info` or `xbps-query`
+pub(crate) fn split_name_version(package: &str) -> Option<(String, String)> {
+    package
+        .rsplit_once('-')
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_parse_name_then_version() {
+        assert_eq!(
+            Some((String::from("ripgrep"), String::from("14.1.0-1"))),
+            name_then_version("ripgrep 14.1.0-1")
+        );
+        assert_eq!(
+            Some((String::from("curl"), String::from("8.5.0"))),
+            name_then_version("curl\t8.5.0")
+        );
+        assert_eq!(None, name_then_version("orphan"));
+    }
+
+    #[test]
+    fn it_can_split_name_version() {
+        assert_eq!(
+            Some((String::from("xdg-utils"), String::from("1.2.1_1"))),
+            split_name_version("xdg-utils-1.2.1_1")
+        );
+        assert_eq!(None, split_name_version("bash"));
+    }
+
+    #[test]
+    fn it_can_detect_the_default_provider() {
+        let mut contexts: Contexts = std::collections::BTreeMap::new();
+        contexts.insert(
+            String::from("os"),
+            std::collections::BTreeMap::from([(
+                String::from("distribution"),
+                crate::values::Value::from("Fedora"),
+            )]),
+        );
+
+        assert_eq!(
+            Some(PackageProviders::Dnf),
+            PackageProviders::detect(&contexts)
+        );
+        assert_eq!(
+            PackageProviders::Homebrew,
+            PackageProviders::resolve(&Some(PackageProviders::Homebrew), &contexts).unwrap()
+        );
+        assert_eq!(
+            Some(PackageProviders::Winget),
+            PackageProviders::for_os(os_info::Type::Windows)
+        );
+
+        contexts.clear();
+        assert!(PackageProviders::resolve(&None, &contexts).is_err());
+    }
+}
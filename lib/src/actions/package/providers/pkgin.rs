@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, split_name_version, PackageProvider};
 use crate::actions::package::repository::PackageRepository;
 use crate::contexts::Contexts;
 use crate::steps::finalizers::FlowControl::StopIf;
@@ -52,6 +52,12 @@ impl PackageProvider for Pkgin {
         Ok(package.packages())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        installed_from_command("pkg_info", &[], |line| {
+            line.split_whitespace().next().and_then(split_name_version)
+        })
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let privilege_provider =
             utilities::get_privilege_provider(&contexts).unwrap_or_else(|| "sudo".to_string());
@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, name_then_version, PackageProvider};
 use crate::actions::package::repository::PackageRepository;
 use crate::contexts::Contexts;
 use crate::steps::Step;
@@ -63,6 +63,17 @@ impl PackageProvider for Snapcraft {
         Ok(package.packages())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        // Skip the "Name Version Rev Tracking Publisher Notes" header
+        installed_from_command("snap", &["list"], |line| {
+            if line.starts_with("Name ") {
+                return None;
+            }
+
+            name_then_version(line)
+        })
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let privilege_provider =
             utilities::get_privilege_provider(&contexts).unwrap_or_else(|| "sudo".to_string());
@@ -100,7 +111,7 @@ mod test {
                 name: Some(String::from("")),
                 list: vec![],
                 extra_args: vec![],
-                provider: PackageProviders::Snapcraft,
+                provider: Some(PackageProviders::Snapcraft),
                 file: false,
             },
             &contexts,
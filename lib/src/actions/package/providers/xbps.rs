@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, split_name_version, PackageProvider};
 use crate::actions::package::repository::PackageRepository;
 use crate::actions::package::PackageVariant;
 use crate::atoms::command::Exec;
@@ -92,6 +92,12 @@ impl PackageProvider for Xbps {
             .collect())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        installed_from_command("xbps-query", &["--list-pkgs"], |line| {
+            line.split_whitespace().nth(1).and_then(split_name_version)
+        })
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let need_installed = self.query(package)?;
         if need_installed.is_empty() {
@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{installed_from_command, name_then_version, PackageProvider};
 use crate::actions::package::repository::PackageRepository;
 use crate::actions::package::PackageVariant;
 use crate::atoms::command::Exec;
@@ -124,6 +124,10 @@ impl PackageProvider for Yay {
             .collect())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        installed_from_command("pacman", &["-Q"], name_then_version)
+    }
+
     fn install(&self, package: &PackageVariant, _contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         // Does not require privilege escalation?
 
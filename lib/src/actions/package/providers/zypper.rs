@@ -1,4 +1,4 @@
-use super::PackageProvider;
+use super::{rpm_installed, PackageProvider};
 use crate::actions::package::{repository::PackageRepository, PackageVariant};
 use crate::atoms::command::Exec;
 use crate::contexts::Contexts;
@@ -46,6 +46,10 @@ impl PackageProvider for Zypper {
         Ok(package.packages())
     }
 
+    fn installed(&self) -> anyhow::Result<Vec<(String, String)>> {
+        rpm_installed()
+    }
+
     fn install(&self, package: &PackageVariant, contexts: &Contexts) -> anyhow::Result<Vec<Step>> {
         let privilege_provider =
             utilities::get_privilege_provider(&contexts).unwrap_or_else(|| "sudo".to_string());
@@ -84,7 +88,7 @@ mod test {
                 name: Some(String::from("")),
                 list: vec![],
                 extra_args: vec![],
-                provider: PackageProviders::Zypper,
+                provider: Some(PackageProviders::Zypper),
                 file: false,
             },
             &contexts,
@@ -251,6 +251,18 @@ impl Display for RepoOrDir {
     }
 }
 
+impl RepoOrDir {
+    /// The source, with the path of a `dir` resolved against `manifest`
+    fn resolve(&self, manifest: &Manifest) -> Result<RepoOrDir> {
+        match self {
+            RepoOrDir::Dir(dir) => Ok(RepoOrDir::Dir(Dir {
+                dir: dir.dir.resolve(manifest)?.display().to_string().into(),
+            })),
+            source => Ok(source.clone()),
+        }
+    }
+}
+
 impl Source for RepoOrDir {
     fn source(&self) -> Result<String> {
         match self {
@@ -290,8 +302,8 @@ pub struct Plugin {
 }
 
 impl Plugin {
-    fn runtime(&self, contexts: Option<Contexts>) -> Result<PluginSpec> {
-        get_plugin(&self.source, contexts).map_err(anyhow::Error::from)
+    fn runtime(&self, source: &RepoOrDir, contexts: Option<Contexts>) -> Result<PluginSpec> {
+        get_plugin(source, contexts).map_err(anyhow::Error::from)
     }
 }
 
@@ -303,15 +315,22 @@ impl Display for Plugin {
 
 impl Action for Plugin {
     fn summarize(&self) -> String {
-        self.runtime(None)
+        // There's no manifest to resolve `@files/` and `@manifest/` against
+        // here, so plugins in those get the generic summary
+        let source = self
+            .source
+            .resolve(&Manifest::default())
+            .unwrap_or_else(|_| self.source.clone());
+
+        self.runtime(&source, None)
             .as_ref()
             .map(PluginSpec::summary)
             .unwrap_or("Ran plugin".to_string())
     }
 
     #[instrument(skip_all)]
-    fn plan(&self, _manifest: &Manifest, context: &Contexts) -> Result<Vec<Step>> {
-        let runtime = self.runtime(Some(context.to_owned()))?;
+    fn plan(&self, manifest: &Manifest, context: &Contexts) -> Result<Vec<Step>> {
+        let runtime = self.runtime(&self.source.resolve(manifest)?, Some(context.to_owned()))?;
         let mut actions = Vec::new();
 
         for action in self.actions.clone() {
@@ -426,4 +445,43 @@ return {
 
         Ok(())
     }
+
+    #[test]
+    fn plugin_dir_is_resolved() -> Result<()> {
+        let temp_dir = tempdir()?;
+        std::fs::write(
+            temp_dir.path().join("plugin.lua"),
+            r#"
+return {
+    name = "noop",
+    summary = "Does nothing",
+    actions = {
+        noop = {
+            plan = function() end,
+            exec = function() end,
+        },
+    },
+}
+            "#,
+        )?;
+
+        let mut manifest = Manifest::deserialize(json!({
+            "actions": [{
+                "action": "plugin",
+                "dir": "@manifest/plugin.lua",
+                "actions": { "noop": {} }
+            }]
+        }))?;
+        manifest.root_dir = Some(temp_dir.path().to_path_buf());
+
+        let steps = manifest
+            .actions
+            .first()
+            .unwrap()
+            .plan(&manifest, &Contexts::default())?;
+
+        assert_eq!(steps.len(), 1);
+
+        Ok(())
+    }
 }
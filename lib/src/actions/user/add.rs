@@ -1,3 +1,4 @@
+use super::providers::UserProviders;
 use super::User;
 use super::UserVariant;
 use crate::actions::Action;
@@ -16,9 +17,9 @@ impl Action for UserAdd {
         format!("Adding user: {}", self.username)
     }
 
-    fn plan(&self, _manifest: &Manifest, context: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let variant: UserVariant = self.into();
-        let box_provider = variant.provider.clone().get_provider();
+    fn plan(&self, manifest: &Manifest, context: &Contexts) -> anyhow::Result<Vec<Step>> {
+        let variant = UserVariant::resolve(self, context).resolve_paths(manifest)?;
+        let box_provider = UserProviders::resolve(&variant.provider, context).get_provider();
         let provider = box_provider.deref();
 
         let mut atoms: Vec<Step> = vec![];
@@ -17,7 +17,7 @@ pub struct UserAddGroup {
     pub group: Vec<String>,
 
     #[serde(default)]
-    pub provider: UserProviders,
+    pub provider: Option<UserProviders>,
 }
 
 impl Action for UserAddGroup {
@@ -30,7 +30,7 @@ impl Action for UserAddGroup {
     }
 
     fn plan(&self, _manifest: &Manifest, context: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let box_provider = self.provider.clone().get_provider();
+        let box_provider = UserProviders::resolve(&self.provider, context).get_provider();
         let provider = box_provider.deref();
 
         let mut atoms: Vec<Step> = vec![];
@@ -2,22 +2,27 @@ pub mod add;
 pub mod add_group;
 pub mod providers;
 
+use crate::actions::{variant_key::find_variant, VariantKey};
+use crate::contexts::Contexts;
+use crate::manifests::Manifest;
+use crate::utilities::CustomPathBuf;
+use indexmap::IndexMap;
 use providers::UserProviders;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use tracing::debug;
 
 #[derive(JsonSchema, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct User {
+    /// Defaults to the user provider for the OS in the `os` context
     #[serde(default)]
-    provider: UserProviders,
+    provider: Option<UserProviders>,
 
     #[serde(default)]
     username: String,
 
     #[serde(default)]
-    home_dir: String,
+    home_dir: CustomPathBuf,
 
     #[serde(default)]
     fullname: String,
@@ -29,19 +34,20 @@ pub struct User {
     group: Vec<String>,
 
     #[serde(default)]
-    variants: HashMap<os_info::Type, UserVariant>,
+    variants: IndexMap<VariantKey, UserVariant>,
 }
 
 #[derive(JsonSchema, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct UserVariant {
+    /// Defaults to the user provider for the OS in the `os` context
     #[serde(default)]
-    provider: UserProviders,
+    provider: Option<UserProviders>,
 
     #[serde(default)]
     username: String,
 
     #[serde(default)]
-    home_dir: String,
+    home_dir: CustomPathBuf,
 
     #[serde(default)]
     fullname: String,
@@ -53,12 +59,10 @@ pub struct UserVariant {
     group: Vec<String>,
 }
 
-impl From<&User> for UserVariant {
-    fn from(user: &User) -> Self {
-        let os = os_info::get();
-
-        // Check for variant configuration for this OS
-        let variant = user.variants.get(&os.os_type());
+impl UserVariant {
+    /// The user, overlaid with the first variant matching the contexts
+    fn resolve(user: &User, contexts: &Contexts) -> Self {
+        let variant = find_variant(&user.variants, contexts);
 
         // No variant overlays
         if variant.is_none() {
@@ -90,4 +94,56 @@ impl From<&User> for UserVariant {
 
         user
     }
+
+    /// The user with `~`, variables and prefixes expanded in `home_dir`
+    fn resolve_paths(mut self, manifest: &Manifest) -> anyhow::Result<Self> {
+        if !self.home_dir.as_str().is_empty() {
+            self.home_dir = self
+                .home_dir
+                .resolve(manifest)?
+                .display()
+                .to_string()
+                .into();
+        }
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn it_resolves_the_home_dir() -> anyhow::Result<()> {
+        let manifest = Manifest {
+            root_dir: Some(PathBuf::from("/manifests/users")),
+            ..Default::default()
+        };
+
+        std::env::set_var("COMTRYA_USER_HOME_TEST", "/srv/homes");
+
+        let user = User {
+            username: String::from("teal'c"),
+            home_dir: "$COMTRYA_USER_HOME_TEST/tealc".into(),
+            ..Default::default()
+        };
+        let variant = UserVariant::resolve(&user, &Contexts::default()).resolve_paths(&manifest)?;
+        assert_eq!("/srv/homes/tealc", variant.home_dir);
+
+        let user = User {
+            home_dir: "@manifest/home".into(),
+            ..Default::default()
+        };
+        let variant = UserVariant::resolve(&user, &Contexts::default()).resolve_paths(&manifest)?;
+        assert_eq!("/manifests/users/home", variant.home_dir);
+
+        let variant = UserVariant::resolve(&User::default(), &Contexts::default())
+            .resolve_paths(&manifest)?;
+        assert_eq!("", variant.home_dir);
+
+        Ok(())
+    }
 }
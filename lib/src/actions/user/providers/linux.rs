@@ -31,10 +31,10 @@ impl UserProvider for LinuxUserProvider {
 
         args.push(user.username.clone());
 
-        if !user.home_dir.is_empty() {
+        if !user.home_dir.as_str().is_empty() {
             args.push(String::from("-m"));
             args.push(String::from("-d"));
-            args.push(user.home_dir.clone());
+            args.push(user.home_dir.to_string());
         }
 
         if !user.shell.is_empty() {
@@ -138,7 +138,7 @@ mod test {
             &UserVariant {
                 username: String::from("test"),
                 shell: String::from("sh"),
-                home_dir: String::from("/home/test"),
+                home_dir: "/home/test".into(),
                 fullname: String::from("Test User"),
                 group: vec![],
                 ..Default::default()
@@ -157,7 +157,7 @@ mod test {
             &UserVariant {
                 username: String::from(""),
                 shell: String::from("sh"),
-                home_dir: String::from("/home/test"),
+                home_dir: "/home/test".into(),
                 fullname: String::from("Test User"),
                 group: vec![],
                 ..Default::default()
@@ -192,7 +192,7 @@ mod test {
             &UserVariant {
                 username: String::from("test"),
                 shell: String::from(""),
-                home_dir: String::from(""),
+                home_dir: "".into(),
                 fullname: String::from(""),
                 group: vec![String::from("testgroup")],
                 ..Default::default()
@@ -142,7 +142,7 @@ mod test {
             &UserVariant {
                 username: String::from("test"),
                 shell: String::from("sh"),
-                home_dir: String::from("/home/test"),
+                home_dir: "/home/test".into(),
                 fullname: String::from("Test User"),
                 group: vec![],
                 ..Default::default()
@@ -161,7 +161,7 @@ mod test {
             &UserVariant {
                 username: String::from(""),
                 shell: String::from("sh"),
-                home_dir: String::from("/home/test"),
+                home_dir: "/home/test".into(),
                 fullname: String::from("Test User"),
                 group: vec![],
                 ..Default::default()
@@ -196,7 +196,7 @@ mod test {
             &UserVariant {
                 username: String::from("test"),
                 shell: String::from(""),
-                home_dir: String::from(""),
+                home_dir: "".into(),
                 fullname: String::from(""),
                 group: vec![String::from("testgroup")],
                 ..Default::default()
@@ -11,7 +11,7 @@ use self::linux::LinuxUserProvider;
 mod macos;
 use self::macos::MacOSUserProvider;
 
-use crate::contexts::Contexts;
+use crate::contexts::{get, Contexts};
 
 #[derive(JsonSchema, Clone, Debug, Serialize, Deserialize)]
 pub enum UserProviders {
@@ -39,21 +39,20 @@ impl UserProviders {
     }
 }
 
-#[allow(clippy::derivable_impls)]
-impl Default for UserProviders {
-    #[cfg(target_os = "linux")]
-    fn default() -> Self {
-        UserProviders::Linux
-    }
-
-    #[cfg(not(target_os = "linux"))]
-    fn default() -> Self {
-        let info = os_info::get();
+impl UserProviders {
+    /// The provider a manifest asked for, or the one for the OS in `os.name`
+    pub fn resolve(provider: &Option<Self>, contexts: &Contexts) -> Self {
+        if let Some(provider) = provider {
+            return provider.clone();
+        }
 
-        match info.os_type() {
-            // BSD Operating systems
-            os_info::Type::FreeBSD => UserProviders::FreeBSD,
-            os_info::Type::Macos => UserProviders::MacOs,
+        match get(contexts, "os.name")
+            .map(|name| name.to_string())
+            .as_deref()
+        {
+            Some("linux") => UserProviders::Linux,
+            Some("freebsd") => UserProviders::FreeBSD,
+            Some("macos") => UserProviders::MacOs,
             _ => UserProviders::None,
         }
     }
@@ -0,0 +1,118 @@
+use crate::contexts::{get, to_rhai, Contexts};
+use crate::rhai_functions;
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// Selects a variant of `package.install` or `user.add`. Either an OS type
+/// such as `Ubuntu`, the historic shorthand, or a Rhai expression over the
+/// contexts, e.g. `hardware.arch == "aarch64"`.
+#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum VariantKey {
+    Os(#[schemars(with = "String")] os_info::Type),
+    Expression(String),
+}
+
+impl VariantKey {
+    fn matches(&self, contexts: &Contexts, scope: &mut rhai::Scope) -> bool {
+        match self {
+            VariantKey::Os(os_type) => get(contexts, "os.distribution")
+                .is_some_and(|distribution| distribution.to_string() == os_type.to_string()),
+            VariantKey::Expression(expression) => {
+                match rhai_functions::eval::<bool>(scope, expression) {
+                    Ok(matches) => matches,
+                    Err(error) => {
+                        error!("Failed execution condition for variant: {}", error);
+                        false
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The first variant, in the order they're written, whose key matches
+pub(crate) fn find_variant<'a, V>(
+    variants: &'a IndexMap<VariantKey, V>,
+    contexts: &Contexts,
+) -> Option<&'a V> {
+    if variants.is_empty() {
+        return None;
+    }
+
+    let mut scope = to_rhai(contexts);
+
+    variants
+        .iter()
+        .find(|(key, _)| key.matches(contexts, &mut scope))
+        .map(|(_, variant)| variant)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn it_can_find_variants() {
+        let variants: IndexMap<VariantKey, String> = serde_yml::from_str(
+            r#"
+os.version == "24.04": noble
+Ubuntu: ubuntu
+hardware.arch == "aarch64": arm
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&VariantKey::Os(os_info::Type::Ubuntu)),
+            variants.keys().nth(1)
+        );
+
+        let mut contexts: Contexts = BTreeMap::new();
+        contexts.insert(
+            String::from("os"),
+            BTreeMap::from([
+                (String::from("distribution"), Value::from("Ubuntu")),
+                (String::from("version"), Value::from("22.04")),
+            ]),
+        );
+        contexts.insert(
+            String::from("hardware"),
+            BTreeMap::from([(String::from("arch"), Value::from("aarch64"))]),
+        );
+
+        assert_eq!(
+            Some(&String::from("ubuntu")),
+            find_variant(&variants, &contexts)
+        );
+
+        contexts
+            .get_mut("os")
+            .unwrap()
+            .insert(String::from("version"), Value::from("24.04"));
+
+        assert_eq!(
+            Some(&String::from("noble")),
+            find_variant(&variants, &contexts)
+        );
+
+        contexts
+            .get_mut("os")
+            .unwrap()
+            .insert(String::from("distribution"), Value::from("Fedora"));
+        contexts
+            .get_mut("os")
+            .unwrap()
+            .insert(String::from("version"), Value::from("40"));
+
+        assert_eq!(
+            Some(&String::from("arm")),
+            find_variant(&variants, &contexts)
+        );
+    }
+}
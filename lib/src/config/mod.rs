@@ -1,21 +1,238 @@
-use crate::contexts::privilege::Privilege;
+use crate::actions::package::PackageProviders;
+use crate::contexts::{os::OsOverride, privilege::Privilege};
+use crate::values::Value;
+use anyhow::anyhow;
+use globset::Glob;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::PathBuf};
+use tracing::warn;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default)]
     pub manifest_paths: Vec<String>,
 
+    /// The first of the `manifest_paths`, resolved. Its `vars/` directory is
+    /// layered into the variables. It's resolved once per run, as that may
+    /// clone a repository.
+    #[serde(skip)]
+    pub manifest_root: Option<PathBuf>,
+
     #[serde(default)]
     pub variables: BTreeMap<String, String>,
 
+    /// Host groups, each layering `vars/groups/<name>.yaml` on matching hosts
+    #[serde(default)]
+    pub groups: Vec<HostGroup>,
+
+    /// Variable files passed with `--vars-file`, layered in the given order
+    #[serde(skip)]
+    pub vars_files: Vec<PathBuf>,
+
+    /// Variables defined on the command line, these override every other layer
+    #[serde(skip)]
+    pub defines: BTreeMap<String, Value>,
+
     #[serde(default)]
     pub include_variables: Option<Vec<String>>,
 
+    /// Where to look for manifests below the manifest paths
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+
     #[serde(default)]
     pub disable_update_check: bool,
 
     #[serde(default)]
     pub privilege: Privilege,
+
+    /// Opt-in `packages` context, listing the installed packages
+    #[serde(default)]
+    pub packages: Option<PackagesConfig>,
+
+    /// Seconds to cache the contexts of a provider for, by prefix,
+    /// e.g. `include_variables: 3600`
+    #[serde(default)]
+    pub context_cache: BTreeMap<String, u64>,
+
+    /// Ignore cached contexts for this run
+    #[serde(skip)]
+    pub refresh_contexts: bool,
+
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Rhai expression over the contexts, returning the name of the profile
+    /// to use when none is selected on the command line
+    #[serde(default)]
+    pub default_profile: Option<String>,
+
+    /// The profile selected for this run
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+
+    /// Pretend to be another OS for this run. Serialized, but never read
+    /// from the config, so cached contexts aren't shared with the real OS.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub os_override: Option<OsOverride>,
+}
+
+impl Config {
+    pub fn profile(&self) -> Option<&Profile> {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+    }
+
+    /// Selects a profile for this run, including its variable includes
+    pub fn select_profile(&mut self, name: &str) -> anyhow::Result<()> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown profile '{}'", name))?;
+
+        if !profile.include_variables.is_empty() {
+            self.include_variables
+                .get_or_insert_with(Vec::new)
+                .extend(profile.include_variables.iter().cloned());
+        }
+
+        self.active_profile = Some(name.to_string());
+
+        Ok(())
+    }
+}
+
+/// A named bundle of manifest selectors, labels and variables
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Profile {
+    /// Manifest names or globs to apply, e.g. `work.*`
+    #[serde(default)]
+    pub manifests: Vec<String>,
+
+    /// Only apply manifests carrying at least one of these labels
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub include_variables: Vec<String>,
+}
+
+impl Profile {
+    pub fn selects(&self, manifest_name: &str) -> bool {
+        self.manifests
+            .iter()
+            .any(|selector| match Glob::new(selector) {
+                Ok(glob) => glob.compile_matcher().is_match(manifest_name),
+                Err(error) => {
+                    warn!("Invalid manifest selector '{}': {}", selector, error);
+                    false
+                }
+            })
+    }
+
+    pub fn labelled(&self, labels: &[String]) -> bool {
+        self.labels.is_empty() || self.labels.iter().any(|label| labels.contains(label))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HostGroup {
+    pub name: String,
+
+    /// Hostname globs, e.g. `work-*`
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackagesConfig {
+    /// Query the package provider detected for this OS
+    #[serde(default = "default_true")]
+    pub default_provider: bool,
+
+    /// Additional package providers to query, e.g. `snapcraft`
+    #[serde(default)]
+    pub providers: Vec<PackageProviders>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DiscoveryConfig {
+    /// How many directories deep manifests may be
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+
+    /// Names of directories that never have manifests in them, at any depth
+    #[serde(default = "default_exclude")]
+    pub exclude: Vec<String>,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_depth(),
+            exclude: default_exclude(),
+        }
+    }
+}
+
+fn default_max_depth() -> usize {
+    9
+}
+
+fn default_exclude() -> Vec<String> {
+    vec![String::from("files")]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_can_select_a_profile() -> anyhow::Result<()> {
+        let mut config: Config = serde_yml::from_str(
+            r#"
+include_variables:
+  - file+yaml:///etc/comtrya/common.yaml
+profiles:
+  work:
+    manifests: ["work.*", "shell"]
+    labels: ["laptop"]
+    variables:
+      email: daniel@sgc.mil
+    include_variables:
+      - dns+txt://work.example.com
+"#,
+        )?;
+
+        assert!(config.profile().is_none());
+        assert!(config.select_profile("home").is_err());
+
+        config.select_profile("work")?;
+
+        let profile = config.profile().expect("work profile to be selected");
+
+        assert!(profile.selects("work.vpn"));
+        assert!(profile.selects("shell"));
+        assert!(!profile.selects("games"));
+        assert!(profile.labelled(&[String::from("laptop")]));
+        assert!(!profile.labelled(&[String::from("desktop")]));
+
+        assert_eq!(
+            Some(vec![
+                String::from("file+yaml:///etc/comtrya/common.yaml"),
+                String::from("dns+txt://work.example.com"),
+            ]),
+            config.include_variables
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,194 @@
+use crate::{
+    config::Config,
+    contexts::{Context, ContextProvider},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{trace, warn};
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheEntry {
+    /// Hash of the configuration the contexts were computed with
+    fingerprint: u64,
+    /// Seconds since the UNIX epoch
+    created: u64,
+    contexts: Vec<Context>,
+}
+
+/// Runs the provider, unless its contexts were cached less than the TTL
+/// configured in `context_cache` ago
+pub(crate) fn get_contexts(
+    config: &Config,
+    provider: &dyn ContextProvider,
+) -> Result<Vec<Context>> {
+    match cache_dir() {
+        Some(cache_dir) => get_contexts_in(&cache_dir, config, provider),
+        None => provider.get_contexts(),
+    }
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs_next::cache_dir().map(|cache_dir| cache_dir.join("comtrya").join("contexts"))
+}
+
+fn get_contexts_in(
+    cache_dir: &Path,
+    config: &Config,
+    provider: &dyn ContextProvider,
+) -> Result<Vec<Context>> {
+    let prefix = provider.get_prefix();
+
+    let ttl = match config.context_cache.get(&prefix) {
+        Some(ttl) => Duration::from_secs(*ttl),
+        None => return provider.get_contexts(),
+    };
+
+    let path = cache_dir.join(format!("{}.json", prefix));
+    let fingerprint = fingerprint(config);
+
+    if !config.refresh_contexts {
+        if let Some(contexts) = read(&path, fingerprint, ttl) {
+            trace!(context = prefix.as_str(), message = "Using cached contexts");
+            return Ok(contexts);
+        }
+    }
+
+    let contexts = provider.get_contexts()?;
+
+    if let Err(error) = write(&path, fingerprint, &contexts) {
+        warn!("Unable to cache the {} contexts: {}", prefix, error);
+    }
+
+    Ok(contexts)
+}
+
+fn read(path: &Path, fingerprint: u64, ttl: Duration) -> Option<Vec<Context>> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let entry: CacheEntry = serde_json::from_str(&contents)
+        .map_err(|error| trace!("Ignoring unreadable cache {}: {}", path.display(), error))
+        .ok()?;
+
+    let age = now().saturating_sub(entry.created);
+
+    if entry.fingerprint != fingerprint || age >= ttl.as_secs() {
+        return None;
+    }
+
+    Some(entry.contexts)
+}
+
+fn write(path: &Path, fingerprint: u64, contexts: &[Context]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let entry = CacheEntry {
+        fingerprint,
+        created: now(),
+        contexts: contexts.to_vec(),
+    };
+
+    std::fs::write(path, serde_json::to_string(&entry)?)?;
+
+    Ok(())
+}
+
+/// Cached contexts are only valid for the configuration they were computed
+/// with, e.g. changing `include_variables` invalidates them
+fn fingerprint(config: &Config) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(config)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+
+    hasher.finish()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::{cell::Cell, collections::BTreeMap};
+
+    struct CountingProvider {
+        calls: Cell<u64>,
+    }
+
+    impl ContextProvider for CountingProvider {
+        fn get_prefix(&self) -> String {
+            String::from("counting")
+        }
+
+        fn get_contexts(&self) -> Result<Vec<Context>> {
+            self.calls.set(self.calls.get() + 1);
+
+            Ok(vec![Context::KeyValueContext(
+                String::from("calls"),
+                self.calls.get().into(),
+            )])
+        }
+    }
+
+    #[test]
+    fn it_can_cache_contexts() -> anyhow::Result<()> {
+        let cache_dir = tempfile::tempdir()?;
+        let provider = CountingProvider {
+            calls: Cell::new(0),
+        };
+
+        let mut config = Config {
+            context_cache: BTreeMap::from([(String::from("counting"), 3600)]),
+            ..Default::default()
+        };
+
+        let first = get_contexts_in(cache_dir.path(), &config, &provider)?;
+        let second = get_contexts_in(cache_dir.path(), &config, &provider)?;
+
+        assert_eq!(first, second);
+        assert_eq!(1, provider.calls.get());
+
+        config.refresh_contexts = true;
+        get_contexts_in(cache_dir.path(), &config, &provider)?;
+        assert_eq!(2, provider.calls.get());
+
+        config.refresh_contexts = false;
+        config
+            .variables
+            .insert(String::from("ship"), String::from("Daedalus"));
+        get_contexts_in(cache_dir.path(), &config, &provider)?;
+        assert_eq!(3, provider.calls.get());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_only_caches_configured_providers() -> anyhow::Result<()> {
+        let cache_dir = tempfile::tempdir()?;
+        let provider = CountingProvider {
+            calls: Cell::new(0),
+        };
+
+        let config = Config::default();
+
+        get_contexts_in(cache_dir.path(), &config, &provider)?;
+        get_contexts_in(cache_dir.path(), &config, &provider)?;
+
+        assert_eq!(2, provider.calls.get());
+        assert!(!cache_dir.path().join("counting.json").exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::contexts::{Context, ContextProvider};
+use anyhow::Result;
+use dirs_next::home_dir;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+pub struct DesktopContextProvider {}
+
+impl ContextProvider for DesktopContextProvider {
+    fn get_prefix(&self) -> String {
+        String::from("desktop")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<super::Context>> {
+        let home = home_dir().unwrap_or_default();
+
+        Ok(vec![
+            Context::ListContext(
+                String::from("current_desktop"),
+                env::var("XDG_CURRENT_DESKTOP")
+                    .unwrap_or_default()
+                    .split(':')
+                    .filter(|desktop| !desktop.is_empty())
+                    .map(Into::into)
+                    .collect(),
+            ),
+            Context::KeyValueContext(
+                String::from("session_type"),
+                session_type(
+                    env::var("XDG_SESSION_TYPE").ok(),
+                    env::var("WAYLAND_DISPLAY").ok(),
+                    env::var("DISPLAY").ok(),
+                )
+                .into(),
+            ),
+            Context::KeyValueContext(
+                String::from("display_manager"),
+                display_manager()
+                    .unwrap_or_else(|| String::from("unknown"))
+                    .into(),
+            ),
+            Context::KeyValueContext(
+                String::from("terminal"),
+                env::var("TERMINAL")
+                    .ok()
+                    .or_else(|| alternative("x-terminal-emulator"))
+                    .or_else(|| env::var("TERM_PROGRAM").ok())
+                    .unwrap_or_else(|| String::from("unknown"))
+                    .into(),
+            ),
+            Context::KeyValueContext(
+                String::from("editor"),
+                env::var("VISUAL")
+                    .ok()
+                    .or_else(|| env::var("EDITOR").ok())
+                    .or_else(|| alternative("editor"))
+                    .unwrap_or_else(|| String::from("unknown"))
+                    .into(),
+            ),
+            Context::KeyValueContext(
+                String::from("xdg_config_home"),
+                xdg_home(env::var("XDG_CONFIG_HOME").ok(), &home, ".config").into(),
+            ),
+            Context::KeyValueContext(
+                String::from("xdg_data_home"),
+                xdg_home(env::var("XDG_DATA_HOME").ok(), &home, ".local/share").into(),
+            ),
+            Context::KeyValueContext(
+                String::from("xdg_state_home"),
+                xdg_home(env::var("XDG_STATE_HOME").ok(), &home, ".local/state").into(),
+            ),
+            Context::KeyValueContext(
+                String::from("xdg_cache_home"),
+                xdg_home(env::var("XDG_CACHE_HOME").ok(), &home, ".cache").into(),
+            ),
+            Context::KeyValueContext(
+                String::from("xdg_runtime_dir"),
+                env::var("XDG_RUNTIME_DIR")
+                    .unwrap_or_else(|_| String::from("unknown"))
+                    .into(),
+            ),
+            Context::ListContext(
+                String::from("xdg_config_dirs"),
+                xdg_dirs(env::var("XDG_CONFIG_DIRS").ok(), "/etc/xdg")
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            ),
+            Context::ListContext(
+                String::from("xdg_data_dirs"),
+                xdg_dirs(
+                    env::var("XDG_DATA_DIRS").ok(),
+                    "/usr/local/share:/usr/share",
+                )
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            ),
+        ])
+    }
+}
+
+/// Prefers what the session manager told us and falls back to
+/// sniffing the display sockets
+fn session_type(
+    session_type: Option<String>,
+    wayland_display: Option<String>,
+    display: Option<String>,
+) -> String {
+    match (session_type, wayland_display, display) {
+        (Some(session_type), _, _) if !session_type.is_empty() => session_type,
+        (_, Some(wayland_display), _) if !wayland_display.is_empty() => String::from("wayland"),
+        (_, _, Some(display)) if !display.is_empty() => String::from("x11"),
+        _ => String::from("tty"),
+    }
+}
+
+/// Per the XDG base directory specification, relative paths are
+/// invalid and must be ignored
+fn xdg_home(value: Option<String>, home: &Path, default: &str) -> PathBuf {
+    value
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .unwrap_or_else(|| home.join(default))
+}
+
+fn xdg_dirs(value: Option<String>, default: &str) -> Vec<String> {
+    let dirs: Vec<String> = value
+        .unwrap_or_default()
+        .split(':')
+        .filter(|path| Path::new(path).is_absolute())
+        .map(String::from)
+        .collect();
+
+    if dirs.is_empty() {
+        return default.split(':').map(String::from).collect();
+    }
+
+    dirs
+}
+
+fn display_manager() -> Option<String> {
+    if let Ok(service) = std::fs::read_link("/etc/systemd/system/display-manager.service") {
+        return service
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string());
+    }
+
+    std::fs::read_to_string("/etc/X11/default-display-manager")
+        .ok()
+        .and_then(|path| {
+            Path::new(path.trim())
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+}
+
+/// Resolves a Debian style `/etc/alternatives` entry to the binary name
+fn alternative(name: &str) -> Option<String> {
+    std::fs::canonicalize(Path::new("/etc/alternatives").join(name))
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_prefix() {
+        let desktopcontext = DesktopContextProvider {};
+        let prefix = desktopcontext.get_prefix();
+        assert_eq!(String::from("desktop"), prefix);
+    }
+
+    #[test]
+    fn it_can_detect_session_type() {
+        assert_eq!(
+            "wayland",
+            session_type(Some("wayland".into()), None, Some(":0".into()))
+        );
+        assert_eq!(
+            "wayland",
+            session_type(None, Some("wayland-1".into()), Some(":0".into()))
+        );
+        assert_eq!("x11", session_type(None, None, Some(":0".into())));
+        assert_eq!("tty", session_type(Some("".into()), None, None));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_can_resolve_xdg_directories() {
+        let home = PathBuf::from("/home/daniel");
+
+        assert_eq!(
+            PathBuf::from("/home/daniel/.config"),
+            xdg_home(None, &home, ".config")
+        );
+        assert_eq!(
+            PathBuf::from("/home/daniel/.local/share"),
+            xdg_home(Some("relative/share".into()), &home, ".local/share")
+        );
+        assert_eq!(
+            PathBuf::from("/srv/config"),
+            xdg_home(Some("/srv/config".into()), &home, ".config")
+        );
+
+        assert_eq!(vec![String::from("/etc/xdg")], xdg_dirs(None, "/etc/xdg"));
+        assert_eq!(
+            vec![String::from("/opt/share"), String::from("/usr/share")],
+            xdg_dirs(
+                Some("/opt/share:relative:/usr/share".into()),
+                "/usr/local/share:/usr/share"
+            )
+        );
+    }
+}
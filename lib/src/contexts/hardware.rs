@@ -0,0 +1,34 @@
+use crate::contexts::{Context, ContextProvider};
+use anyhow::Result;
+
+pub struct HardwareContextProvider {}
+
+impl ContextProvider for HardwareContextProvider {
+    fn get_prefix(&self) -> String {
+        String::from("hardware")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<super::Context>> {
+        let cpus = std::thread::available_parallelism()
+            .map(|cpus| cpus.get() as u64)
+            .unwrap_or(1);
+
+        Ok(vec![
+            Context::KeyValueContext(String::from("arch"), std::env::consts::ARCH.into()),
+            Context::KeyValueContext(String::from("cpus"), cpus.into()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_prefix() {
+        let hardwarecontext = HardwareContextProvider {};
+        let prefix = hardwarecontext.get_prefix();
+        assert_eq!(String::from("hardware"), prefix);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::contexts::{Context, ContextProvider};
+use anyhow::Result;
+use std::{env, path::Path};
+
+pub struct LocaleContextProvider {}
+
+impl ContextProvider for LocaleContextProvider {
+    fn get_prefix(&self) -> String {
+        String::from("locale")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<super::Context>> {
+        let lang = env::var("LANG").unwrap_or_else(|_| String::from("unknown"));
+        let (language, territory, encoding) = split_locale(&lang);
+
+        Ok(vec![
+            Context::KeyValueContext(String::from("lang"), lang.clone().into()),
+            Context::KeyValueContext(String::from("language"), language.into()),
+            Context::KeyValueContext(String::from("territory"), territory.into()),
+            Context::KeyValueContext(String::from("encoding"), encoding.into()),
+            Context::KeyValueContext(
+                String::from("timezone"),
+                timezone().unwrap_or_else(|| String::from("unknown")).into(),
+            ),
+            Context::KeyValueContext(
+                String::from("keyboard_layout"),
+                keyboard_layout()
+                    .unwrap_or_else(|| String::from("unknown"))
+                    .into(),
+            ),
+        ])
+    }
+}
+
+/// Splits `language[_territory][.encoding][@modifier]`, e.g. `en_GB.UTF-8`
+fn split_locale(lang: &str) -> (String, String, String) {
+    let lang = lang.split_once('@').map_or(lang, |(lang, _)| lang);
+    let (lang, encoding) = lang.split_once('.').unwrap_or((lang, "unknown"));
+    let (language, territory) = lang.split_once('_').unwrap_or((lang, "unknown"));
+
+    (
+        language.to_string(),
+        territory.to_string(),
+        encoding.to_string(),
+    )
+}
+
+fn timezone() -> Option<String> {
+    if let Ok(tz) = env::var("TZ") {
+        if !tz.is_empty() {
+            return Some(tz.trim_start_matches(':').to_string());
+        }
+    }
+
+    if let Ok(target) = std::fs::read_link("/etc/localtime") {
+        if let Some(timezone) = zone_from_path(&target) {
+            return Some(timezone);
+        }
+    }
+
+    std::fs::read_to_string("/etc/timezone")
+        .ok()
+        .map(|timezone| timezone.trim().to_string())
+        .filter(|timezone| !timezone.is_empty())
+}
+
+/// `/etc/localtime` links into the zoneinfo database, e.g.
+/// `/usr/share/zoneinfo/Europe/Berlin`
+fn zone_from_path(path: &Path) -> Option<String> {
+    let path = path.to_string_lossy();
+
+    path.split_once("zoneinfo/")
+        .map(|(_, timezone)| timezone.to_string())
+}
+
+fn keyboard_layout() -> Option<String> {
+    if let Ok(layout) = env::var("XKB_DEFAULT_LAYOUT") {
+        if !layout.is_empty() {
+            return Some(layout);
+        }
+    }
+
+    [
+        "/etc/default/keyboard",
+        "/etc/vconsole.conf",
+        "/etc/X11/xorg.conf.d/00-keyboard.conf",
+    ]
+    .iter()
+    .filter_map(|path| std::fs::read_to_string(path).ok())
+    .find_map(|contents| parse_keyboard_layout(&contents))
+}
+
+/// Understands the shell style `XKBLAYOUT=` / `KEYMAP=` files and the
+/// `Option "XkbLayout"` lines written by `localectl`
+fn parse_keyboard_layout(contents: &str) -> Option<String> {
+    let unquote = |value: &str| value.trim().trim_matches('"').to_string();
+
+    contents.lines().map(str::trim).find_map(|line| {
+        let layout = line
+            .strip_prefix("XKBLAYOUT=")
+            .or_else(|| line.strip_prefix("KEYMAP="))
+            .or_else(|| {
+                line.strip_prefix("Option")
+                    .map(str::trim)
+                    .and_then(|option| option.strip_prefix("\"XkbLayout\""))
+            })
+            .map(unquote)?;
+
+        // An empty value leaves the layout unset, keep looking
+        (!layout.is_empty()).then_some(layout)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_prefix() {
+        let localecontext = LocaleContextProvider {};
+        let prefix = localecontext.get_prefix();
+        assert_eq!(String::from("locale"), prefix);
+    }
+
+    #[test]
+    fn it_can_split_locale() {
+        assert_eq!(
+            (
+                String::from("en"),
+                String::from("GB"),
+                String::from("UTF-8")
+            ),
+            split_locale("en_GB.UTF-8")
+        );
+
+        assert_eq!(
+            (
+                String::from("de"),
+                String::from("DE"),
+                String::from("unknown")
+            ),
+            split_locale("de_DE@euro")
+        );
+
+        assert_eq!(
+            (
+                String::from("C"),
+                String::from("unknown"),
+                String::from("unknown")
+            ),
+            split_locale("C")
+        );
+    }
+
+    #[test]
+    fn it_can_find_zone_from_path() {
+        assert_eq!(
+            Some(String::from("Europe/Berlin")),
+            zone_from_path(Path::new("/usr/share/zoneinfo/Europe/Berlin"))
+        );
+
+        assert_eq!(
+            Some(String::from("America/New_York")),
+            zone_from_path(Path::new("/var/db/timezone/zoneinfo/America/New_York"))
+        );
+
+        assert_eq!(None, zone_from_path(Path::new("/etc/localtime")));
+    }
+
+    #[test]
+    fn it_can_parse_keyboard_layout() {
+        assert_eq!(
+            Some(String::from("de")),
+            parse_keyboard_layout("XKBMODEL=\"pc105\"\nXKBLAYOUT=\"de\"\nXKBVARIANT=\"\"\n")
+        );
+
+        assert_eq!(
+            Some(String::from("us")),
+            parse_keyboard_layout("KEYMAP=us\nFONT=eurlatgr\n")
+        );
+
+        assert_eq!(
+            Some(String::from("gb")),
+            parse_keyboard_layout(
+                "Section \"InputClass\"\n        Option \"XkbLayout\" \"gb\"\nEndSection\n"
+            )
+        );
+
+        assert_eq!(
+            Some(String::from("fr")),
+            parse_keyboard_layout("XKBLAYOUT=\"\"\nKEYMAP=fr\n")
+        );
+
+        assert_eq!(None, parse_keyboard_layout("XKBLAYOUT=\"\"\n"));
+    }
+}
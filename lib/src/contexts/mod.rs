@@ -1,4 +1,5 @@
 use anyhow::Result;
+use regex::Regex;
 use rhai::Scope;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -9,14 +10,28 @@ use crate::contexts::privilege::PrivilegeContextProvider;
 use crate::{
     config::Config,
     contexts::{
-        env::EnvContextProvider, os::OSContextProvider,
+        desktop::DesktopContextProvider, env::EnvContextProvider,
+        hardware::HardwareContextProvider, locale::LocaleContextProvider,
+        network::NetworkContextProvider, os::OSContextProvider, packages::PackagesContextProvider,
         variable_include::VariableIncludeContextProvider, variables::VariablesContextProvider,
     },
     values::Value,
 };
 
+/// Caches the contexts of slow providers between runs
+mod cache;
+/// Desktop context provider: desktop environment, session and XDG base directories
+pub mod desktop;
 pub mod env;
+/// Hardware context provider: CPU architecture and count
+pub mod hardware;
+/// Locale context provider: language, timezone and keyboard layout
+pub mod locale;
+/// Network context provider: hostname, domain, interfaces and DNS search domains
+pub mod network;
 pub mod os;
+/// Packages context provider: installed packages, opt-in via `packages` in the config
+pub mod packages;
 pub mod privilege;
 /// User context provider: understands the user running the command
 pub mod user;
@@ -26,6 +41,12 @@ pub mod variables;
 pub trait ContextProvider {
     fn get_prefix(&self) -> String;
     fn get_contexts(&self) -> Result<Vec<Context>>;
+
+    /// Lazy providers are slow, so `build_contexts_for` only runs them
+    /// when their prefix is referenced
+    fn is_lazy(&self) -> bool {
+        false
+    }
 }
 
 pub type Contexts = BTreeMap<String, BTreeMap<String, Value>>;
@@ -36,24 +57,59 @@ pub enum Context {
     ListContext(String, Vec<Value>),
 }
 
+/// Runs every provider. The `variables` context is the merge of all
+/// variable layers, see [`variables::variable_layers`] for their precedence.
 #[instrument(skip(config))]
 pub fn build_contexts(config: &Config) -> Contexts {
+    build(config, |_| true)
+}
+
+/// Like `build_contexts`, but lazy providers are only run when their prefix
+/// is referenced in one of the `sources`, e.g. the manifests' templates
+/// and `where` conditions
+#[instrument(skip(config, sources))]
+pub fn build_contexts_for(config: &Config, sources: &[String]) -> Contexts {
+    build(config, |prefix| references(sources, prefix))
+}
+
+fn references(sources: &[String], prefix: &str) -> bool {
+    match Regex::new(&format!(r"\b{}\b", regex::escape(prefix))) {
+        Ok(regex) => sources.iter().any(|source| regex.is_match(source)),
+        Err(_) => true,
+    }
+}
+
+fn build(config: &Config, referenced: impl Fn(&str) -> bool) -> Contexts {
     let mut contexts: Contexts = BTreeMap::new();
 
     let context_providers: Vec<Box<dyn ContextProvider>> = vec![
         Box::new(UserContextProvider {}),
-        Box::new(OSContextProvider {}),
+        Box::new(OSContextProvider { config }),
+        Box::new(HardwareContextProvider {}),
+        Box::new(NetworkContextProvider {}),
+        Box::new(DesktopContextProvider {}),
+        Box::new(LocaleContextProvider {}),
         Box::new(EnvContextProvider {}),
         Box::new(VariablesContextProvider { config }),
         Box::new(VariableIncludeContextProvider { config }),
         Box::new(PrivilegeContextProvider { config }),
+        Box::new(PackagesContextProvider { config }),
     ];
 
     context_providers.iter().for_each(|provider| {
         let mut values: BTreeMap<String, Value> = BTreeMap::new();
 
-        provider
-            .get_contexts()
+        if provider.is_lazy() && !referenced(&provider.get_prefix()) {
+            trace!(
+                context = provider.get_prefix().as_str(),
+                message = "Skipping unreferenced context"
+            );
+            contexts.insert(provider.get_prefix(), values);
+
+            return;
+        }
+
+        cache::get_contexts(config, provider.as_ref())
             .map_err(|e| {
                 warn!(
                     "Error getting contexts from provider: {} -> {}",
@@ -92,6 +148,14 @@ pub fn build_contexts(config: &Config) -> Contexts {
     contexts
 }
 
+/// Looks up a dotted key such as `os.name` or `variables.git.email`,
+/// descending into nested maps
+pub fn get<'a>(contexts: &'a Contexts, key: &str) -> Option<&'a Value> {
+    let (prefix, key) = key.split_once('.')?;
+
+    variables::lookup(contexts.get(prefix)?, key)
+}
+
 pub fn to_tera(contexts: &Contexts) -> tera::Context {
     let mut context = tera::Context::new();
 
@@ -143,6 +207,52 @@ mod test {
         assert_eq!(result, String::from("rawkode"));
     }
 
+    #[test]
+    fn it_can_get_dotted_keys() {
+        let mut contexts: Contexts = BTreeMap::new();
+        let git = BTreeMap::from([(String::from("email"), Value::from("sam@sgc.mil"))]);
+
+        contexts.insert(
+            String::from("variables"),
+            BTreeMap::from([
+                (String::from("git"), Value::from(git)),
+                (String::from("ship.name"), Value::from("Odyssey")),
+            ]),
+        );
+
+        assert_eq!(
+            Some(&Value::from("sam@sgc.mil")),
+            get(&contexts, "variables.git.email")
+        );
+        assert_eq!(
+            Some(&Value::from("Odyssey")),
+            get(&contexts, "variables.ship.name")
+        );
+        assert_eq!(None, get(&contexts, "variables.git.name"));
+        assert_eq!(None, get(&contexts, "os.name"));
+        assert_eq!(None, get(&contexts, "variables"));
+    }
+
+    #[test]
+    fn it_only_runs_referenced_lazy_providers() {
+        let config = Config {
+            include_variables: Some(vec![String::from("nope://sgc.mil")]),
+            ..Default::default()
+        };
+
+        let sources = vec![String::from("where: os.name == \"linux\"")];
+        let contexts = build_contexts_for(&config, &sources);
+
+        assert!(contexts["os"].contains_key("name"));
+        assert!(contexts["network"].is_empty());
+        assert!(contexts["include_variables"].is_empty());
+
+        let sources = vec![String::from("{{ network.hostname }}")];
+        let contexts = build_contexts_for(&config, &sources);
+
+        assert!(contexts["network"].contains_key("hostname"));
+    }
+
     #[test]
     fn variables_context_resolves_from_config() -> anyhow::Result<()> {
         let mut variables = BTreeMap::new();
@@ -0,0 +1,218 @@
+use crate::contexts::{Context, ContextProvider};
+use crate::values::Value;
+use anyhow::Result;
+use gethostname::gethostname;
+use std::collections::BTreeMap;
+use tracing::trace;
+
+pub struct NetworkContextProvider {}
+
+impl ContextProvider for NetworkContextProvider {
+    fn get_prefix(&self) -> String {
+        String::from("network")
+    }
+
+    fn is_lazy(&self) -> bool {
+        true
+    }
+
+    fn get_contexts(&self) -> Result<Vec<super::Context>> {
+        let hostname = gethostname().to_string_lossy().to_string();
+        let fqdn = fqdn(&hostname).unwrap_or_else(|| hostname.clone());
+        let search_domains = search_domains();
+
+        let domain = fqdn
+            .split_once('.')
+            .map(|(_, domain)| domain.to_string())
+            .or_else(|| search_domains.first().cloned())
+            .unwrap_or_else(|| String::from("unknown"));
+
+        Ok(vec![
+            Context::KeyValueContext(String::from("hostname"), hostname.into()),
+            Context::KeyValueContext(String::from("fqdn"), fqdn.into()),
+            Context::KeyValueContext(String::from("domain"), domain.into()),
+            Context::KeyValueContext(String::from("interfaces"), interfaces()?),
+            Context::KeyValueContext(
+                String::from("default_interface"),
+                default_interface()
+                    .map(Into::into)
+                    .unwrap_or_else(|| "unknown".into()),
+            ),
+            Context::ListContext(
+                String::from("search_domains"),
+                search_domains.into_iter().map(Into::into).collect(),
+            ),
+        ])
+    }
+}
+
+/// Maps every interface name to its `ipv4` and `ipv6` addresses
+fn interfaces() -> Result<Value> {
+    let mut interfaces: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+
+    for interface in if_addrs::get_if_addrs()? {
+        let addresses = interfaces.entry(interface.name.clone()).or_insert_with(|| {
+            BTreeMap::from([
+                (String::from("ipv4"), vec![]),
+                (String::from("ipv6"), vec![]),
+            ])
+        });
+
+        let family = match interface.addr {
+            if_addrs::IfAddr::V4(_) => "ipv4",
+            if_addrs::IfAddr::V6(_) => "ipv6",
+        };
+
+        if let Some(addresses) = addresses.get_mut(family) {
+            addresses.push(interface.ip().to_string());
+        }
+    }
+
+    Ok(interfaces.into())
+}
+
+#[cfg(unix)]
+fn fqdn(hostname: &str) -> Option<String> {
+    let hints = dns_lookup::AddrInfoHints {
+        flags: libc::AI_CANONNAME,
+        ..Default::default()
+    };
+
+    dns_lookup::getaddrinfo(Some(hostname), None, Some(hints))
+        .map_err(|error| trace!("Unable to resolve canonical hostname: {:?}", error))
+        .ok()?
+        .filter_map(Result::ok)
+        .find_map(|addrinfo| addrinfo.canonname)
+}
+
+#[cfg(not(unix))]
+fn fqdn(_hostname: &str) -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn search_domains() -> Vec<String> {
+    std::fs::read_to_string("/etc/resolv.conf")
+        .map(|contents| parse_resolv_conf(&contents))
+        .unwrap_or_default()
+}
+
+#[cfg(not(unix))]
+fn search_domains() -> Vec<String> {
+    vec![]
+}
+
+#[cfg(target_os = "linux")]
+fn default_interface() -> Option<String> {
+    std::fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|contents| parse_proc_net_route(&contents))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn default_interface() -> Option<String> {
+    let output = std::process::Command::new("route")
+        .args(["-n", "get", "default"])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("interface:"))
+        .map(|interface| interface.trim().to_string())
+}
+
+#[cfg(not(unix))]
+fn default_interface() -> Option<String> {
+    None
+}
+
+/// The last `search` or `domain` directive wins, as documented in resolv.conf(5)
+#[cfg_attr(not(unix), allow(dead_code))]
+fn parse_resolv_conf(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.starts_with(';'))
+        .fold(vec![], |domains, line| {
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("search") | Some("domain") => words.map(String::from).collect(),
+                _ => domains,
+            }
+        })
+}
+
+/// Picks the interface of the default route (destination `00000000`)
+/// with the lowest metric
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_net_route(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+
+            match columns.as_slice() {
+                [interface, "00000000", _, _, _, _, metric, ..] => {
+                    Some((metric.parse::<u64>().unwrap_or(u64::MAX), *interface))
+                }
+                _ => None,
+            }
+        })
+        .min()
+        .map(|(_, interface)| interface.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_prefix() {
+        let networkcontext = NetworkContextProvider {};
+        let prefix = networkcontext.get_prefix();
+        assert_eq!(String::from("network"), prefix);
+    }
+
+    #[test]
+    fn it_can_parse_resolv_conf() {
+        let contents = r#"
+# Generated by NetworkManager
+domain atlantis.example.com
+search corp.example.com example.com
+nameserver 10.0.0.1
+"#;
+
+        assert_eq!(
+            vec![
+                String::from("corp.example.com"),
+                String::from("example.com")
+            ],
+            parse_resolv_conf(contents)
+        );
+
+        assert_eq!(
+            Vec::<String>::new(),
+            parse_resolv_conf("nameserver 1.1.1.1")
+        );
+    }
+
+    #[test]
+    fn it_can_parse_proc_net_route() {
+        let contents = r#"Iface	Destination	Gateway 	Flags	RefCnt	Use	Metric	Mask		MTU	Window	IRTT
+wlp3s0	00000000	0100A8C0	0003	0	0	600	00000000	0	0	0
+enp0s31f6	00000000	0100A8C0	0003	0	0	100	00000000	0	0	0
+enp0s31f6	0000A8C0	00000000	0001	0	0	100	00FFFFFF	0	0	0
+"#;
+
+        assert_eq!(
+            Some(String::from("enp0s31f6")),
+            parse_proc_net_route(contents)
+        );
+
+        assert_eq!(None, parse_proc_net_route("Iface\tDestination\n"));
+    }
+}
@@ -1,42 +1,116 @@
+use crate::config::Config;
 use crate::contexts::{Context, ContextProvider};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use gethostname::gethostname;
-use os_info;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
 
-pub struct OSContextProvider {}
+/// Detecting the OS reads files and runs commands, so it only happens once
+static OS_INFO: OnceLock<os_info::Info> = OnceLock::new();
 
-impl ContextProvider for OSContextProvider {
+fn detected() -> &'static os_info::Info {
+    OS_INFO.get_or_init(os_info::get)
+}
+
+/// Pretend to be another OS, set with `--os-override TYPE[:VERSION]`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OsOverride {
+    pub os_type: os_info::Type,
+    pub version: Option<String>,
+}
+
+/// Parses `TYPE[:VERSION]`, where the type is spelt as in variant keys,
+/// e.g. `Ubuntu:24.04` or `Macos`
+pub fn parse_os_override(os: &str) -> Result<OsOverride> {
+    let (os_type, version) = match os.split_once(':') {
+        Some((os_type, version)) => (os_type, Some(version.to_string())),
+        None => (os, None),
+    };
+
+    let os_type =
+        serde_json::from_value(serde_json::Value::String(os_type.to_string())).map_err(|_| {
+            anyhow!(
+                "unknown OS type `{}`, e.g. Ubuntu, Fedora or Macos",
+                os_type
+            )
+        })?;
+
+    Ok(OsOverride { os_type, version })
+}
+
+/// The OS type, as detected or overridden
+pub fn os_type(config: &Config) -> os_info::Type {
+    match &config.os_override {
+        Some(os) => os.os_type,
+        None => detected().os_type(),
+    }
+}
+
+/// The OS name, as in `std::env::consts::OS`, e.g. `linux` or `macos`
+pub fn os_name(config: &Config) -> &'static str {
+    match &config.os_override {
+        Some(os) => match os.os_type {
+            os_info::Type::Macos => "macos",
+            os_info::Type::Windows => "windows",
+            os_info::Type::FreeBSD => "freebsd",
+            os_info::Type::NetBSD => "netbsd",
+            os_info::Type::OpenBSD => "openbsd",
+            os_info::Type::DragonFly => "dragonfly",
+            os_info::Type::Android => "android",
+            os_info::Type::Illumos => "illumos",
+            _ => "linux",
+        },
+        None => std::env::consts::OS,
+    }
+}
+
+pub struct OSContextProvider<'a> {
+    pub config: &'a Config,
+}
+
+impl<'a> ContextProvider for OSContextProvider<'a> {
     fn get_prefix(&self) -> String {
         String::from("os")
     }
 
     fn get_contexts(&self) -> Result<Vec<super::Context>> {
-        let osinfo = os_info::get();
+        let osinfo = detected();
+        let name = os_name(self.config);
+        let family = match name {
+            "windows" => "windows",
+            _ => "unix",
+        };
+
+        let (version, codename, edition) = match &self.config.os_override {
+            Some(os) => (
+                os.version
+                    .clone()
+                    .unwrap_or_else(|| String::from("unknown")),
+                "unknown",
+                "unknown",
+            ),
+            None => (
+                format!("{}", osinfo.version()),
+                osinfo.codename().unwrap_or("unknown"),
+                osinfo.edition().unwrap_or("unknown"),
+            ),
+        };
 
         Ok(vec![
             Context::KeyValueContext(String::from("hostname"), gethostname().into()),
-            Context::KeyValueContext(String::from("family"), std::env::consts::FAMILY.into()),
-            Context::KeyValueContext(String::from("name"), std::env::consts::OS.into()),
+            Context::KeyValueContext(String::from("family"), family.into()),
+            Context::KeyValueContext(String::from("name"), name.into()),
             Context::KeyValueContext(
                 String::from("distribution"),
-                format!("{}", osinfo.os_type()).into(),
-            ),
-            Context::KeyValueContext(
-                String::from("codename"),
-                String::from(osinfo.codename().unwrap_or("unknown")).into(),
+                format!("{}", os_type(self.config)).into(),
             ),
+            Context::KeyValueContext(String::from("codename"), codename.into()),
             Context::KeyValueContext(
                 String::from("bitness"),
                 format!("{}", osinfo.bitness()).into(),
             ),
-            Context::KeyValueContext(
-                String::from("version"),
-                format!("{}", osinfo.version()).into(),
-            ),
-            Context::KeyValueContext(
-                String::from("edition"),
-                String::from(osinfo.edition().unwrap_or("unknown")).into(),
-            ),
+            Context::KeyValueContext(String::from("version"), version.into()),
+            Context::KeyValueContext(String::from("edition"), edition.into()),
         ])
     }
 }
@@ -44,18 +118,54 @@ impl ContextProvider for OSContextProvider {
 #[cfg(test)]
 mod test {
     use super::*;
+    use pretty_assertions::assert_eq;
 
     #[test]
     fn it_can_prefix() {
-        let oscontext = OSContextProvider {};
+        let config = Config::default();
+        let oscontext = OSContextProvider { config: &config };
         let prefix = oscontext.get_prefix();
         assert_eq!(String::from("os"), prefix);
     }
 
+    #[test]
+    fn it_can_override_the_os() -> anyhow::Result<()> {
+        assert!(parse_os_override("Plan9").is_err());
+
+        let config = Config {
+            os_override: Some(parse_os_override("Macos:14.5")?),
+            ..Default::default()
+        };
+
+        let contexts = OSContextProvider { config: &config }.get_contexts()?;
+        let value = |key: &str| {
+            contexts.iter().find_map(|context| match context {
+                Context::KeyValueContext(k, v) if k == key => Some(v.to_string()),
+                _ => None,
+            })
+        };
+
+        assert_eq!(Some(String::from("Mac OS")), value("distribution"));
+        assert_eq!(Some(String::from("14.5")), value("version"));
+        assert_eq!(Some(String::from("macos")), value("name"));
+        assert_eq!(Some(String::from("unix")), value("family"));
+
+        let config = Config {
+            os_override: Some(parse_os_override("Windows")?),
+            ..Default::default()
+        };
+
+        assert_eq!(os_info::Type::Windows, os_type(&config));
+        assert_eq!("windows", os_name(&config));
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(target_os = "macos")]
     fn it_can_macos() {
-        let oscontext = OSContextProvider {};
+        let config = Config::default();
+        let oscontext = OSContextProvider { config: &config };
         let keyvaluepairs = oscontext.get_contexts().unwrap();
 
         keyvaluepairs.iter().for_each(|context| match context {
@@ -73,7 +183,8 @@ mod test {
     #[test]
     #[cfg(windows)]
     fn it_can_windows() {
-        let oscontext = OSContextProvider {};
+        let config = Config::default();
+        let oscontext = OSContextProvider { config: &config };
         let keyvaluepairs = oscontext.get_contexts().unwrap();
 
         keyvaluepairs.iter().for_each(|context| match context {
@@ -91,7 +202,8 @@ mod test {
     #[test]
     #[cfg(target_os = "linux")]
     fn it_can_linux() {
-        let oscontext = OSContextProvider {};
+        let config = Config::default();
+        let oscontext = OSContextProvider { config: &config };
         let keyvaluepairs = oscontext.get_contexts().unwrap();
 
         keyvaluepairs.iter().for_each(|context| match context {
@@ -109,7 +221,8 @@ mod test {
     #[test]
     #[cfg(target_os = "freebsd")]
     fn it_can_linux() {
-        let oscontext = OSContextProvider {};
+        let config = Config::default();
+        let oscontext = OSContextProvider { config: &config };
         let keyvaluepairs = oscontext.get_contexts().unwrap();
 
         keyvaluepairs.iter().for_each(|context| match context {
@@ -0,0 +1,156 @@
+use crate::actions::package::PackageProviders;
+use crate::config::Config;
+use crate::contexts::{os, Context, ContextProvider};
+use anyhow::Result;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Mutex, OnceLock},
+};
+use tracing::warn;
+
+pub struct PackagesContextProvider<'a> {
+    pub config: &'a Config,
+}
+
+impl<'a> ContextProvider for PackagesContextProvider<'a> {
+    fn get_prefix(&self) -> String {
+        String::from("packages")
+    }
+
+    fn is_lazy(&self) -> bool {
+        true
+    }
+
+    fn get_contexts(&self) -> Result<Vec<super::Context>> {
+        let Some(packages) = &self.config.packages else {
+            return Ok(vec![]);
+        };
+
+        let mut providers = vec![];
+
+        if packages.default_provider {
+            match PackageProviders::for_os(os::os_type(self.config)) {
+                Some(provider) => providers.push(provider),
+                None => warn!("No default package provider for this OS, skipping it"),
+            }
+        }
+
+        for provider in packages.providers.iter() {
+            if !providers.contains(provider) {
+                providers.push(provider.clone());
+            }
+        }
+
+        let mut versions = BTreeMap::new();
+
+        for provider in providers {
+            let name = provider.clone().get_provider().name().to_lowercase();
+
+            match installed(provider) {
+                Ok(installed) => {
+                    versions.insert(name, installed.into_iter().collect());
+                }
+                Err(error) => warn!("Unable to list installed packages: {}", error),
+            }
+        }
+
+        Ok(to_contexts(versions))
+    }
+}
+
+/// Versions are keyed by provider, as the same package can be installed by
+/// more than one of them, each with its own version
+fn to_contexts(versions: BTreeMap<String, BTreeMap<String, String>>) -> Vec<Context> {
+    let installed: BTreeSet<&String> = versions.values().flat_map(BTreeMap::keys).collect();
+
+    vec![
+        Context::ListContext(
+            String::from("installed"),
+            installed.into_iter().cloned().map(Into::into).collect(),
+        ),
+        Context::KeyValueContext(String::from("versions"), versions.into()),
+    ]
+}
+
+type InstalledCache = Mutex<HashMap<String, Vec<(String, String)>>>;
+
+/// Package queries are slow, so each provider is only asked once per run
+fn installed(provider: PackageProviders) -> Result<Vec<(String, String)>> {
+    static CACHE: OnceLock<InstalledCache> = OnceLock::new();
+
+    let provider = provider.get_provider();
+    let cache = CACHE.get_or_init(Default::default);
+
+    if let Some(installed) = cache
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(provider.name()).cloned())
+    {
+        return Ok(installed);
+    }
+
+    let installed = provider.installed()?;
+
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(provider.name().to_string(), installed.clone());
+    }
+
+    Ok(installed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_prefix() {
+        let config = Config::default();
+        let packagescontext = PackagesContextProvider { config: &config };
+        let prefix = packagescontext.get_prefix();
+        assert_eq!(String::from("packages"), prefix);
+    }
+
+    #[test]
+    fn it_is_opt_in() -> anyhow::Result<()> {
+        let config = Config::default();
+        let packagescontext = PackagesContextProvider { config: &config };
+
+        assert_eq!(Vec::<Context>::new(), packagescontext.get_contexts()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keys_versions_by_provider() {
+        let versions = BTreeMap::from([
+            (
+                String::from("aptitude"),
+                BTreeMap::from([
+                    (String::from("git"), String::from("1:2.43.0-1")),
+                    (String::from("curl"), String::from("8.5.0-2")),
+                ]),
+            ),
+            (
+                String::from("snapcraft"),
+                BTreeMap::from([(String::from("git"), String::from("2.45.1"))]),
+            ),
+        ]);
+
+        let contexts = to_contexts(versions.clone());
+
+        assert_eq!(
+            Context::ListContext(
+                String::from("installed"),
+                vec![Value::from("curl"), Value::from("git")]
+            ),
+            contexts[0]
+        );
+
+        assert_eq!(
+            Context::KeyValueContext(String::from("versions"), Value::from(versions)),
+            contexts[1]
+        );
+    }
+}
@@ -17,6 +17,10 @@ impl<'a> ContextProvider for VariableIncludeContextProvider<'a> {
         String::from("include_variables")
     }
 
+    fn is_lazy(&self) -> bool {
+        true
+    }
+
     fn get_contexts(&self) -> Result<Vec<super::Context>> {
         let mut contexts = HashMap::<String, String>::new();
 
@@ -1,8 +1,13 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context as _, Result};
+use gethostname::gethostname;
+use globset::Glob;
+use std::{collections::BTreeMap, path::Path};
+use tracing::{trace, warn};
 
 use crate::{
     config::Config,
-    contexts::{Context, ContextProvider},
+    contexts::{os, Context, ContextProvider},
+    values::Value,
 };
 
 pub struct VariablesContextProvider<'a> {
@@ -15,15 +20,405 @@ impl<'a> ContextProvider for VariablesContextProvider<'a> {
     }
 
     fn get_contexts(&self) -> Result<Vec<super::Context>> {
-        let mut contexts = vec![];
+        let mut variables = BTreeMap::new();
 
-        for (key, value) in self.config.variables.iter() {
-            contexts.push(Context::KeyValueContext(
-                key.to_owned(),
-                value.to_owned().into(),
-            ));
+        for layer in variable_layers(self.config)? {
+            merge(&mut variables, layer.variables);
         }
 
-        Ok(contexts)
+        Ok(variables
+            .into_iter()
+            .map(|(key, value)| Context::KeyValueContext(key, value))
+            .collect())
+    }
+}
+
+/// A source of variables. Layers are returned in increasing order of
+/// precedence, so later layers override earlier ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableLayer {
+    pub name: String,
+    pub variables: BTreeMap<String, Value>,
+}
+
+/// Layers, lowest precedence first: `Config.variables`, `vars/all.yaml`,
+/// `vars/os/<os.name>.yaml`, `vars/groups/<group>.yaml` for every group
+/// matching this host, `vars/hosts/<hostname>.yaml`, the selected profile,
+/// `--vars-file` files in the order given and finally `-D` defines. Only
+/// the `vars/` of `Config.manifest_root` is layered. Those layers are
+/// optional, but `--vars-file` files that can't be read are an error.
+pub fn variable_layers(config: &Config) -> Result<Vec<VariableLayer>> {
+    let hostname = gethostname().to_string_lossy().to_string();
+
+    let mut layers = vec![VariableLayer {
+        name: String::from("config"),
+        variables: to_values(&config.variables),
+    }];
+
+    if let Some(root) = manifest_root(config) {
+        let mut files = vec![
+            String::from("vars/all.yaml"),
+            format!("vars/os/{}.yaml", os::os_name(config)),
+        ];
+
+        for group in config.groups.iter() {
+            if host_in_group(&hostname, &group.hosts) {
+                files.push(format!("vars/groups/{}.yaml", group.name));
+            }
+        }
+
+        files.push(format!("vars/hosts/{}.yaml", hostname));
+
+        layers.extend(files.iter().filter_map(|file| load_layer(root, file)));
+    }
+
+    if let (Some(name), Some(profile)) = (&config.active_profile, config.profile()) {
+        layers.push(VariableLayer {
+            name: format!("profile {}", name),
+            variables: to_values(&profile.variables),
+        });
+    }
+
+    for file in config.vars_files.iter() {
+        layers.push(load_vars_file(file)?);
+    }
+
+    layers.push(VariableLayer {
+        name: String::from("defines"),
+        variables: config.defines.clone(),
+    });
+
+    Ok(layers)
+}
+
+/// Every layer defining the dotted `key` (without the `variables.` prefix),
+/// with the value it defines. The last entry is the one that wins.
+pub fn explain(config: &Config, key: &str) -> Result<Vec<(String, Value)>> {
+    Ok(variable_layers(config)?
+        .into_iter()
+        .filter_map(|layer| {
+            lookup(&layer.variables, key).map(|value| (layer.name.clone(), value.clone()))
+        })
+        .collect())
+}
+
+pub(crate) fn lookup<'a>(variables: &'a BTreeMap<String, Value>, key: &str) -> Option<&'a Value> {
+    if let Some(value) = variables.get(key) {
+        return Some(value);
+    }
+
+    let (head, rest) = key.split_once('.')?;
+
+    match variables.get(head)? {
+        Value::Map(map) => lookup(map, rest),
+        _ => None,
+    }
+}
+
+/// Parses a `-D` define: `KEY=value`, where `KEY` may be dotted to set a
+/// nested variable (`git.email=sam@sgc.mil`) and may carry a type, e.g.
+/// `jobs:int=8`. Supported types are `str` (the default), `int`, `float`,
+/// `bool` and `json`.
+pub fn parse_define(define: &str) -> Result<(String, Value)> {
+    let (key, value) = define
+        .split_once('=')
+        .ok_or_else(|| anyhow!("invalid KEY=value: no `=` found in `{}`", define))?;
+
+    let (key, kind) = key.split_once(':').unwrap_or((key, "str"));
+
+    if key.is_empty() || key.split('.').any(str::is_empty) {
+        return Err(anyhow!("invalid variable name `{}`", key));
+    }
+
+    let value = match kind {
+        "str" | "string" => Value::from(value),
+        "int" => value
+            .parse::<i64>()
+            .map_err(|error| anyhow!("`{}` is not an int: {}", value, error))?
+            .into(),
+        "float" => value
+            .parse::<f64>()
+            .map_err(|error| anyhow!("`{}` is not a float: {}", value, error))?
+            .into(),
+        "bool" => value
+            .parse::<bool>()
+            .map_err(|error| anyhow!("`{}` is not a bool: {}", value, error))?
+            .into(),
+        "json" => serde_json::from_str::<serde_json::Value>(value)
+            .map_err(|error| anyhow!("`{}` is not valid JSON: {}", value, error))?
+            .try_into()?,
+        kind => {
+            return Err(anyhow!(
+                "unknown type `{}`, expected one of str, int, float, bool or json",
+                kind
+            ))
+        }
+    };
+
+    Ok((key.to_string(), value))
+}
+
+/// Inserts a define, nesting dotted keys into maps
+pub fn define(defines: &mut BTreeMap<String, Value>, key: &str, value: Value) {
+    let value = key.rsplit('.').fold(value, |value, part| {
+        Value::Map(BTreeMap::from([(part.to_string(), value)]))
+    });
+
+    if let Value::Map(layer) = value {
+        merge(defines, layer);
+    }
+}
+
+/// Deep merges `layer` onto `variables`, nested maps are merged key by key
+fn merge(variables: &mut BTreeMap<String, Value>, layer: BTreeMap<String, Value>) {
+    for (key, value) in layer {
+        match (variables.get_mut(&key), value) {
+            (Some(Value::Map(existing)), Value::Map(value)) => merge(existing, value),
+            (_, value) => {
+                variables.insert(key, value);
+            }
+        }
+    }
+}
+
+fn to_values(variables: &BTreeMap<String, String>) -> BTreeMap<String, Value> {
+    variables
+        .iter()
+        .map(|(key, value)| (key.to_owned(), value.to_owned().into()))
+        .collect()
+}
+
+fn host_in_group(hostname: &str, hosts: &[String]) -> bool {
+    hosts.iter().any(|pattern| match Glob::new(pattern) {
+        Ok(glob) => glob.compile_matcher().is_match(hostname),
+        Err(error) => {
+            warn!("Invalid host pattern '{}': {}", pattern, error);
+            false
+        }
+    })
+}
+
+/// The directory of `Config.manifest_root`, which may be a single manifest
+fn manifest_root(config: &Config) -> Option<&Path> {
+    let root = config.manifest_root.as_deref()?;
+
+    match root.is_file() {
+        true => root.parent(),
+        false => Some(root),
+    }
+}
+
+fn load_layer(root: &Path, file: &str) -> Option<VariableLayer> {
+    let contents = match std::fs::read_to_string(root.join(file)) {
+        Ok(contents) => contents,
+        Err(_) => {
+            trace!("No variables layer at {}", file);
+            return None;
+        }
+    };
+
+    match serde_yml::from_str::<Option<BTreeMap<String, Value>>>(&contents) {
+        Ok(variables) => Some(VariableLayer {
+            name: file.to_string(),
+            variables: variables.unwrap_or_default(),
+        }),
+        Err(error) => {
+            warn!(
+                "Skipping variables layer {}, it cannot be parsed: {}",
+                file, error
+            );
+            None
+        }
+    }
+}
+
+/// A file given with `--vars-file`, which has to exist and be valid
+pub fn load_vars_file(file: &Path) -> Result<VariableLayer> {
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read --vars-file {}", file.display()))?;
+
+    let variables = serde_yml::from_str::<Option<BTreeMap<String, Value>>>(&contents)
+        .with_context(|| format!("Failed to parse --vars-file {}", file.display()))?;
+
+    Ok(VariableLayer {
+        name: format!("vars-file {}", file.display()),
+        variables: variables.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::HostGroup;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn it_can_layer_variables() -> anyhow::Result<()> {
+        let hostname = gethostname().to_string_lossy().to_string();
+        let root = tempfile::tempdir()?;
+        let vars = root.path().join("vars");
+
+        fs::create_dir_all(vars.join("os"))?;
+        fs::create_dir_all(vars.join("groups"))?;
+        fs::create_dir_all(vars.join("hosts"))?;
+
+        fs::write(
+            vars.join("all.yaml"),
+            "ship: Daedalus\ncrew:\n  captain: Caldwell\n  pilot: Kleinman\n",
+        )?;
+        fs::write(
+            vars.join("os")
+                .join(format!("{}.yaml", std::env::consts::OS)),
+            "ship: Odyssey\n",
+        )?;
+        fs::write(vars.join("groups").join("sgc.yaml"), "base: Cheyenne\n")?;
+        fs::write(vars.join("groups").join("atlantis.yaml"), "base: Lantea\n")?;
+        fs::write(
+            vars.join("hosts").join(format!("{}.yaml", hostname)),
+            "crew:\n  captain: Ellis\n",
+        )?;
+
+        let vars_file = root.path().join("tauri.yaml");
+        fs::write(&vars_file, "base: Tau'ri\n")?;
+
+        let config = Config {
+            manifest_root: Some(root.path().to_path_buf()),
+            variables: BTreeMap::from([
+                (String::from("ship"), String::from("Prometheus")),
+                (String::from("gate"), String::from("Milky Way")),
+            ]),
+            groups: vec![
+                HostGroup {
+                    name: String::from("sgc"),
+                    hosts: vec![String::from("*")],
+                },
+                HostGroup {
+                    name: String::from("atlantis"),
+                    hosts: vec![String::from("never-*")],
+                },
+            ],
+            vars_files: vec![vars_file],
+            defines: BTreeMap::from([(String::from("gate"), Value::from("Pegasus"))]),
+            ..Default::default()
+        };
+
+        let contexts: BTreeMap<String, Value> = VariablesContextProvider { config: &config }
+            .get_contexts()?
+            .into_iter()
+            .filter_map(|context| match context {
+                Context::KeyValueContext(key, value) => Some((key, value)),
+                Context::ListContext(_, _) => None,
+            })
+            .collect();
+
+        assert_eq!("Odyssey", contexts["ship"].to_string());
+        assert_eq!("Pegasus", contexts["gate"].to_string());
+        assert_eq!("Tau'ri", contexts["base"].to_string());
+        assert_eq!("captain=Ellis,pilot=Kleinman", contexts["crew"].to_string());
+
+        let explained: Vec<String> = explain(&config, "crew.captain")?
+            .into_iter()
+            .map(|(layer, value)| format!("{layer}={value}"))
+            .collect();
+
+        assert_eq!(
+            vec![
+                String::from("vars/all.yaml=Caldwell"),
+                format!("vars/hosts/{hostname}.yaml=Ellis"),
+            ],
+            explained
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_fails_on_vars_files_it_cannot_read() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let broken = root.path().join("broken.yaml");
+        fs::write(&broken, "base: [Cheyenne\n")?;
+
+        for file in [root.path().join("missing.yaml"), broken] {
+            let config = Config {
+                manifest_root: Some(root.path().to_path_buf()),
+                vars_files: vec![file.clone()],
+                ..Default::default()
+            };
+
+            let error = variable_layers(&config).unwrap_err().to_string();
+            assert!(error.contains(&file.display().to_string()), "{}", error);
+            assert!(VariablesContextProvider { config: &config }
+                .get_contexts()
+                .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_parse_defines() -> anyhow::Result<()> {
+        assert_eq!(
+            (String::from("gate"), Value::from("Pegasus")),
+            parse_define("gate=Pegasus")?
+        );
+        assert_eq!(
+            (String::from("url"), Value::from("https://sgc.mil/?a=b")),
+            parse_define("url:str=https://sgc.mil/?a=b")?
+        );
+        assert_eq!(
+            (String::from("jobs"), Value::from(8i64)),
+            parse_define("jobs:int=8")?
+        );
+        assert_eq!(
+            (String::from("ratio"), Value::from(0.5)),
+            parse_define("ratio:float=0.5")?
+        );
+        assert_eq!(
+            (String::from("debug"), Value::from(true)),
+            parse_define("debug:bool=true")?
+        );
+        assert_eq!(
+            (
+                String::from("ships"),
+                Value::from(vec![Value::from("Daedalus"), Value::from("Odyssey")])
+            ),
+            parse_define(r#"ships:json=["Daedalus", "Odyssey"]"#)?
+        );
+
+        assert!(parse_define("gate").is_err());
+        assert!(parse_define("jobs:int=eight").is_err());
+        assert!(parse_define("jobs:number=8").is_err());
+        assert!(parse_define("git..email=sam@sgc.mil").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_nest_defines() {
+        let mut defines = BTreeMap::new();
+
+        define(&mut defines, "git.email", Value::from("sam@sgc.mil"));
+        define(&mut defines, "git.name", Value::from("Samantha Carter"));
+        define(&mut defines, "jobs", Value::from(8i64));
+
+        assert_eq!(Some(&Value::from(8i64)), lookup(&defines, "jobs"));
+        assert_eq!(
+            Some(&Value::from("sam@sgc.mil")),
+            lookup(&defines, "git.email")
+        );
+        assert_eq!(
+            Some(&Value::from("Samantha Carter")),
+            lookup(&defines, "git.name")
+        );
+    }
+
+    #[test]
+    fn it_can_match_host_groups() {
+        let hosts = vec![String::from("work-*"), String::from("build01")];
+
+        assert!(host_in_group("work-laptop", &hosts));
+        assert!(host_in_group("build01", &hosts));
+        assert!(!host_in_group("home-desktop", &hosts));
     }
 }
@@ -3,6 +3,7 @@ pub mod atoms;
 pub mod config;
 pub mod contexts;
 pub mod manifests;
+pub mod rhai_functions;
 pub mod steps;
 pub mod tera_functions;
 mod utilities;
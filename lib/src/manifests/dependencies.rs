@@ -0,0 +1,358 @@
+use super::Manifest;
+use anyhow::{anyhow, Result};
+use globset::Glob;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tracing::{error, trace};
+
+/// Resolves a `depends` or `after` entry of the manifest `name` to the names
+/// of the manifests it refers to. Entries may be relative to the manifest,
+/// `./fonts` or `../shell`, globs such as `fonts.*`, and optional when
+/// prefixed with `?`, in which case nothing matching isn't an error.
+pub fn resolve_dependency(name: &str, dependency: &str, names: &[&str]) -> Result<Vec<String>> {
+    let (optional, dependency) = match dependency.strip_prefix('?') {
+        Some(dependency) => (true, dependency),
+        None => (false, dependency),
+    };
+
+    let dependency = relative_to(name, dependency);
+
+    let resolved: Vec<String> = if is_glob(&dependency) {
+        let glob = Glob::new(&dependency)
+            .map_err(|err| anyhow!("Invalid dependency '{}' of '{}': {}", dependency, name, err))?
+            .compile_matcher();
+
+        names
+            .iter()
+            .filter(|candidate| **candidate != name && glob.is_match(candidate))
+            .map(|candidate| candidate.to_string())
+            .collect()
+    } else {
+        names
+            .iter()
+            .filter(|candidate| **candidate == dependency)
+            .map(|candidate| candidate.to_string())
+            .collect()
+    };
+
+    if resolved.is_empty() {
+        if optional {
+            trace!(
+                message = "Optional dependency not found",
+                from = name,
+                dependency = dependency.as_str()
+            );
+        } else {
+            return Err(anyhow!(
+                "Unresolved dependency '{}' of manifest '{}'",
+                dependency,
+                name
+            ));
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// `./` is the directory of the manifest and every `../` goes up a
+/// directory, e.g. `../shell` from `dev.go` is `shell`
+fn relative_to(name: &str, dependency: &str) -> String {
+    if !dependency.starts_with("./") && !dependency.starts_with("../") {
+        return dependency.to_string();
+    }
+
+    let (directory, _) = name.rsplit_once('.').unwrap_or((name, ""));
+    let mut parts: Vec<&str> = directory.split('.').collect();
+    let mut rest = dependency;
+
+    loop {
+        if let Some(stripped) = rest.strip_prefix("./") {
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("../") {
+            parts.pop();
+            rest = stripped;
+        } else {
+            break;
+        }
+    }
+
+    let rest = rest.replace('/', ".");
+    parts.push(&rest);
+
+    parts.join(".")
+}
+
+fn is_glob(dependency: &str) -> bool {
+    dependency.contains(['*', '?', '[', '{'])
+}
+
+/// The resolved `depends` and `after` of every manifest, by name
+#[derive(Debug, Default, PartialEq)]
+pub struct Dependencies {
+    /// Run before the manifest, and pulled in when it's selected
+    pub depends: BTreeMap<String, Vec<String>>,
+    /// Run before the manifest, but only if they're selected anyway
+    pub after: BTreeMap<String, Vec<String>>,
+    /// Why a manifest can't run, as one of its dependencies isn't there
+    pub unresolved: BTreeMap<String, String>,
+}
+
+impl Dependencies {
+    /// A dependency that can't be resolved only stops its own manifest, and
+    /// those depending on it, from running
+    pub fn resolve(manifests: &HashMap<String, Manifest>) -> Self {
+        let names: Vec<&str> = manifests.keys().map(String::as_str).collect();
+        let mut dependencies = Dependencies::default();
+
+        for (name, manifest) in manifests.iter() {
+            for (entries, resolved) in [
+                (&manifest.depends, &mut dependencies.depends),
+                (&manifest.after, &mut dependencies.after),
+            ] {
+                let mut names_resolved = vec![];
+
+                for entry in entries.iter() {
+                    match resolve_dependency(name, entry, &names) {
+                        Ok(names) => names_resolved.extend(names),
+                        Err(err) => {
+                            dependencies
+                                .unresolved
+                                .entry(name.clone())
+                                .or_insert_with(|| err.to_string());
+                        }
+                    }
+                }
+
+                resolved.insert(name.clone(), names_resolved);
+            }
+        }
+
+        dependencies
+    }
+
+    /// The order to run manifests in: the selected manifests and everything
+    /// they depend on, each after its `depends` and any selected `after`.
+    /// Manifests with unresolved dependencies are reported and left out,
+    /// along with the manifests depending on them.
+    pub fn order(&self, selected: &[String]) -> Result<Vec<String>> {
+        let mut included = BTreeSet::new();
+        let mut pending: Vec<String> = selected.to_vec();
+
+        while let Some(name) = pending.pop() {
+            if included.insert(name.clone()) {
+                pending.extend(self.depends.get(&name).into_iter().flatten().cloned());
+            }
+        }
+
+        let mut skipped: BTreeSet<String> = BTreeSet::new();
+
+        for (name, err) in self.unresolved.iter() {
+            if included.contains(name) {
+                error!("{}", err);
+                skipped.insert(name.clone());
+            }
+        }
+
+        while let Some((name, dependency)) = included
+            .iter()
+            .filter(|name| !skipped.contains(*name))
+            .find_map(|name| {
+                self.depends
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .find(|dependency| skipped.contains(*dependency))
+                    .map(|dependency| (name.clone(), dependency.clone()))
+            })
+        {
+            error!(
+                "Skipping manifest '{}', its dependency '{}' can't run",
+                name, dependency
+            );
+            skipped.insert(name);
+        }
+
+        included.retain(|name| !skipped.contains(name));
+
+        // What each manifest waits for, among the ones that run
+        let mut waiting: BTreeMap<&str, BTreeSet<&str>> = included
+            .iter()
+            .map(|name| {
+                let before = self
+                    .depends
+                    .get(name)
+                    .into_iter()
+                    .chain(self.after.get(name))
+                    .flatten()
+                    .filter(|dependency| included.contains(*dependency))
+                    .map(String::as_str)
+                    .collect();
+
+                (name.as_str(), before)
+            })
+            .collect();
+
+        let mut order: Vec<String> = vec![];
+
+        // Ready manifests run alphabetically, so the order is stable
+        while let Some(name) = waiting
+            .iter()
+            .find(|(_, before)| before.is_empty())
+            .map(|(name, _)| *name)
+        {
+            waiting.remove(name);
+            waiting.values_mut().for_each(|before| {
+                before.remove(name);
+            });
+
+            order.push(name.to_string());
+        }
+
+        if !waiting.is_empty() {
+            return Err(anyhow!(
+                "Can't order manifests {}, their dependencies form a cycle",
+                waiting.keys().cloned().collect::<Vec<&str>>().join(", ")
+            ));
+        }
+
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const NAMES: [&str; 6] = [
+        "dev",
+        "dev.go",
+        "dev.rust",
+        "fonts.nerd",
+        "fonts.noto",
+        "shell",
+    ];
+
+    #[test]
+    fn it_can_resolve_relative_dependencies() -> Result<()> {
+        assert_eq!(
+            vec!["dev.rust"],
+            resolve_dependency("dev.go", "./rust", &NAMES)?
+        );
+        assert_eq!(vec!["dev.go"], resolve_dependency("dev", "./go", &NAMES)?);
+        assert_eq!(
+            vec!["shell"],
+            resolve_dependency("dev.go", "../shell", &NAMES)?
+        );
+        assert_eq!(
+            vec!["fonts.nerd"],
+            resolve_dependency("dev.go", "../fonts/nerd", &NAMES)?
+        );
+        assert_eq!(vec!["shell"], resolve_dependency("dev", "shell", &NAMES)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_resolve_globs_and_optional_dependencies() -> Result<()> {
+        assert_eq!(
+            vec!["fonts.nerd", "fonts.noto"],
+            resolve_dependency("shell", "fonts.*", &NAMES)?
+        );
+        assert_eq!(
+            vec!["dev.rust"],
+            resolve_dependency("dev.go", "./*", &NAMES)?
+        );
+
+        assert!(resolve_dependency("shell", "editor", &NAMES).is_err());
+        assert!(resolve_dependency("shell", "editor.*", &NAMES).is_err());
+        assert!(resolve_dependency("shell", "?editor", &NAMES)?.is_empty());
+
+        Ok(())
+    }
+
+    fn manifest(depends: &[&str], after: &[&str]) -> Manifest {
+        Manifest {
+            depends: depends.iter().map(|name| name.to_string()).collect(),
+            after: after.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_can_order_manifests() -> Result<()> {
+        let manifests = HashMap::from([
+            (String::from("dev"), manifest(&["./go"], &[])),
+            (String::from("dev.go"), manifest(&[], &["shell"])),
+            (String::from("dev.rust"), manifest(&["?dev.zig"], &[])),
+            (String::from("fonts.nerd"), manifest(&[], &[])),
+            (String::from("shell"), manifest(&["fonts.*"], &[])),
+        ]);
+
+        let dependencies = Dependencies::resolve(&manifests);
+
+        // `after` doesn't pull shell in
+        assert_eq!(
+            vec!["dev.go", "dev"],
+            dependencies.order(&[String::from("dev")])?
+        );
+
+        // but orders it when it's selected
+        let order = dependencies.order(&[String::from("dev"), String::from("shell")])?;
+        let position = |name: &str| order.iter().position(|n| n == name).unwrap();
+
+        assert_eq!(4, order.len());
+        assert!(position("fonts.nerd") < position("shell"));
+        assert!(position("shell") < position("dev.go"));
+        assert!(position("dev.go") < position("dev"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_skips_manifests_with_unresolved_dependencies() -> Result<()> {
+        let manifests = HashMap::from([
+            (String::from("broken"), manifest(&["missing"], &[])),
+            (String::from("dev"), manifest(&["broken"], &[])),
+            (String::from("dev.go"), manifest(&["dev"], &[])),
+            (String::from("fonts"), manifest(&[], &[])),
+            (String::from("shell"), manifest(&[], &["broken"])),
+        ]);
+
+        let dependencies = Dependencies::resolve(&manifests);
+
+        assert_eq!(
+            vec!["broken"],
+            dependencies.unresolved.keys().collect::<Vec<&String>>()
+        );
+
+        // Only the selected manifests are affected
+        assert_eq!(vec!["fonts"], dependencies.order(&[String::from("fonts")])?);
+
+        let all: Vec<String> = manifests.keys().cloned().collect();
+        assert_eq!(vec!["fonts", "shell"], dependencies.order(&all)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reports_cycles() -> Result<()> {
+        let manifests = HashMap::from([
+            (String::from("a"), manifest(&["b"], &[])),
+            (String::from("b"), manifest(&["c"], &[])),
+            (String::from("c"), manifest(&[], &["a"])),
+            (String::from("d"), manifest(&[], &["a"])),
+        ]);
+
+        let dependencies = Dependencies::resolve(&manifests);
+
+        // c is only ordered after a when a runs too
+        assert!(dependencies.order(&[String::from("c")]).is_ok());
+        assert!(dependencies.order(&[String::from("a")]).is_err());
+        assert_eq!(
+            vec!["c", "d"],
+            dependencies.order(&[String::from("c"), String::from("d")])?
+        );
+
+        Ok(())
+    }
+}
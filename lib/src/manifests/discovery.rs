@@ -0,0 +1,214 @@
+use super::format::Format;
+use crate::{config::DiscoveryConfig, tera_functions::TEMPLATES_DIR};
+use anyhow::anyhow;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{DirEntry, WalkBuilder};
+use serde::Deserialize;
+use std::{ffi::OsStr, path::Path};
+use tracing::warn;
+
+/// Gitignore-style patterns of files and directories that aren't manifests,
+/// in any directory of the manifests
+const IGNORE_FILE: &str = ".comtryaignore";
+
+/// An optional list of manifest globs, in the root of the manifests
+const INDEX_FILE: &str = "manifests.yaml";
+
+/// Directories in the root of the manifests that have other files than
+/// manifests in them
+const RESERVED_DIRECTORIES: [&str; 4] = ["vars", "snippets", "roles", TEMPLATES_DIR];
+
+/// Files in the root of the manifests that aren't manifests. The index is
+/// one too, but only when it is an index, see `read_index`.
+const RESERVED_FILES: [&str; 1] = ["Comtrya.yaml"];
+
+/// Every manifest file below `manifest_path`
+pub(super) fn walk(
+    manifest_path: &Path,
+    discovery: &DiscoveryConfig,
+) -> impl Iterator<Item = Result<DirEntry, ignore::Error>> {
+    let mut walker = WalkBuilder::new(manifest_path);
+    let exclude = discovery.exclude.clone();
+
+    walker
+        .standard_filters(true)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .follow_links(false)
+        .same_file_system(true)
+        .max_depth(Some(discovery.max_depth))
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            let reserved = match is_dir {
+                true => RESERVED_DIRECTORIES.as_slice(),
+                false => RESERVED_FILES.as_slice(),
+            };
+
+            let is_reserved = entry.depth() == 1
+                && (reserved.iter().any(|name| entry.file_name() == *name)
+                    || (!is_dir
+                        && entry.file_name() == INDEX_FILE
+                        && read_index(entry.path()).is_some()));
+            let is_excluded = is_dir
+                && exclude
+                    .iter()
+                    .any(|name| entry.file_name() == OsStr::new(name));
+
+            !(is_reserved || is_excluded)
+        })
+        .build()
+        // Don't walk directories
+        .filter(|entry| {
+            !entry
+                .as_ref()
+                .ok()
+                .and_then(|entry| entry.metadata().ok().map(|entry| entry.is_dir()))
+                .unwrap_or(false)
+        })
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .ok()
+                .is_some_and(|entry| Format::of(entry.path()).is_some())
+        })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestIndex {
+    manifests: Vec<String>,
+}
+
+/// The index at `path`, when it's only a `manifests:` list of strings.
+/// Anything else is a manifest that happens to have the name of the index.
+fn read_index(path: &Path) -> Option<ManifestIndex> {
+    serde_yml::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+/// The globs listed in the index of the manifests in `root`, when there's
+/// one. Only files matching them are manifests.
+pub(super) fn index(root: &Path) -> anyhow::Result<Option<GlobSet>> {
+    let path = root.join(INDEX_FILE);
+
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let Some(index) = read_index(&path) else {
+        warn!(
+            "{} isn't an index of manifests, which only has a `manifests:` list, so it's a manifest",
+            path.display()
+        );
+        return Ok(None);
+    };
+
+    let mut globs = GlobSetBuilder::new();
+
+    for glob in &index.manifests {
+        globs.add(
+            Glob::new(glob)
+                .map_err(|err| anyhow!("Invalid glob in {}: {}", path.display(), err))?,
+        );
+    }
+
+    Ok(Some(globs.build()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn manifests(root: &Path, discovery: &DiscoveryConfig) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = walk(root, discovery)
+            .filter_map(Result::ok)
+            .map(|entry| entry.path().strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn it_can_exclude_directories_and_ignore_files() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path();
+
+        for directory in ["ci", "files", "dev/go/nested"] {
+            std::fs::create_dir_all(root.join(directory))?;
+        }
+
+        for file in [
+            "ci/pipeline.yaml",
+            "files/gitconfig.yaml",
+            "dev/go.yaml",
+            "dev/go/nested/deep.yaml",
+            "dev/notes.yaml",
+            "shell.toml",
+        ] {
+            std::fs::write(root.join(file), "")?;
+        }
+
+        std::fs::write(root.join(INDEX_FILE), "manifests: [\"**\"]\n")?;
+
+        std::fs::write(root.join(IGNORE_FILE), "ci/\n")?;
+        std::fs::write(root.join("dev").join(IGNORE_FILE), "notes.yaml\n")?;
+
+        assert_eq!(
+            vec![
+                PathBuf::from("dev/go/nested/deep.yaml"),
+                PathBuf::from("dev/go.yaml"),
+                PathBuf::from("shell.toml"),
+            ],
+            manifests(root, &DiscoveryConfig::default())
+        );
+
+        let discovery = DiscoveryConfig {
+            max_depth: 2,
+            exclude: vec![],
+        };
+
+        assert_eq!(
+            vec![
+                PathBuf::from("dev/go.yaml"),
+                PathBuf::from("files/gitconfig.yaml"),
+                PathBuf::from("shell.toml"),
+            ],
+            manifests(root, &discovery)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_read_the_index() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        assert!(index(root.path())?.is_none());
+
+        std::fs::write(
+            root.path().join(INDEX_FILE),
+            "manifests:\n  - shell.toml\n  - dev/**\n",
+        )?;
+
+        let globs = index(root.path())?.expect("an index");
+        assert!(globs.is_match("shell.toml"));
+        assert!(globs.is_match("dev/go/main.yaml"));
+        assert!(!globs.is_match("ci/pipeline.yaml"));
+
+        std::fs::write(root.path().join(INDEX_FILE), "manifests: [\"dev/[\"]\n")?;
+        assert!(index(root.path()).is_err());
+
+        // A manifest of the same name is a manifest
+        std::fs::write(
+            root.path().join(INDEX_FILE),
+            "manifests: [shell.toml]\nactions: []\n",
+        )?;
+        assert!(index(root.path())?.is_none());
+        assert_eq!(
+            vec![PathBuf::from(INDEX_FILE)],
+            manifests(root.path(), &DiscoveryConfig::default())
+        );
+
+        Ok(())
+    }
+}
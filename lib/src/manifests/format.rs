@@ -0,0 +1,40 @@
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use std::{ffi::OsStr, path::Path};
+
+/// The languages manifests can be written in, by file extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Yaml,
+    Toml,
+    Json,
+    Json5,
+    Lua,
+}
+
+impl Format {
+    pub(crate) const EXTENSIONS: [&'static str; 6] =
+        ["yaml", "yml", "toml", "json", "json5", "lua"];
+
+    pub(crate) fn of(path: &Path) -> Option<Format> {
+        match path.extension().and_then(OsStr::to_str)? {
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            "json5" => Some(Format::Json5),
+            "lua" => Some(Format::Lua),
+            _ => None,
+        }
+    }
+
+    /// Lua manifests are run rather than parsed, see `ManifestSource::evaluate`
+    pub(crate) fn parse<T: DeserializeOwned>(&self, contents: &str) -> anyhow::Result<T> {
+        Ok(match self {
+            Format::Yaml => serde_yml::from_str(contents)?,
+            Format::Toml => toml::from_str(contents)?,
+            Format::Json => serde_json::from_str(contents)?,
+            Format::Json5 => json5::from_str(contents)?,
+            Format::Lua => return Err(anyhow!("Lua manifests can't be parsed")),
+        })
+    }
+}
@@ -0,0 +1,305 @@
+use super::{format::Format, roles, with_values};
+use crate::contexts::{to_tera, Contexts};
+use crate::tera_functions::render;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use std::path::{Path, PathBuf};
+
+/// Snippets and roles nested this deep are most likely including each other
+const MAX_DEPTH: usize = 16;
+
+/// An `action: include`, which is replaced by the actions of a snippet
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnippetInclude {
+    /// Always `include`
+    #[allow(dead_code)]
+    action: String,
+
+    /// The snippet's path below `snippets/`, without its extension
+    snippet: String,
+
+    /// Available to the snippet as `args.<name>`
+    #[serde(default)]
+    with: JsonMap<String, JsonValue>,
+
+    /// Added to the `where` of every action of the snippet
+    #[serde(rename = "where")]
+    condition: Option<String>,
+}
+
+/// Splices the actions of the manifest's `include`s in front of its own
+/// actions, and replaces every `action: include` with the actions of the
+/// snippet it names and every `action: role` with the actions of the role
+pub(crate) fn expand(
+    manifest: &mut JsonMap<String, JsonValue>,
+    root: &Path,
+    contexts: &Contexts,
+) -> Result<()> {
+    let mut actions = included(manifest, root, contexts)?;
+
+    if let Some(JsonValue::Array(own)) = manifest.remove("actions") {
+        actions.extend(own);
+    }
+
+    let actions = expand_actions(actions, root, contexts, 0)?;
+    manifest.insert(String::from("actions"), JsonValue::Array(actions));
+
+    Ok(())
+}
+
+/// Replaces snippets and roles with their actions. `depth` is how deeply
+/// nested in other snippets and roles the actions are.
+pub(crate) fn expand_actions(
+    actions: Vec<JsonValue>,
+    root: &Path,
+    contexts: &Contexts,
+    depth: usize,
+) -> Result<Vec<JsonValue>> {
+    if depth >= MAX_DEPTH {
+        return Err(anyhow!(
+            "Snippets and roles are nested more than {} deep, do they include each other?",
+            MAX_DEPTH
+        ));
+    }
+
+    let mut expanded = vec![];
+
+    for action in actions {
+        if is_snippet(&action) {
+            expanded.extend(expand_snippet(&action, root, contexts, depth)?);
+        } else if roles::is_role(&action) {
+            expanded.extend(roles::expand_role(&action, root, contexts, depth)?);
+        } else {
+            expanded.push(action);
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// The actions of every file in the manifest's `include`, in order
+pub(crate) fn included(
+    manifest: &JsonMap<String, JsonValue>,
+    root: &Path,
+    contexts: &Contexts,
+) -> Result<Vec<JsonValue>> {
+    let includes: Vec<String> = match manifest.get("include") {
+        Some(include) => {
+            serde_json::from_value(include.clone()).context("`include` must be a list of files")?
+        }
+        None => return Ok(vec![]),
+    };
+
+    let mut actions = vec![];
+
+    for include in includes {
+        let path = root.join(&include);
+
+        if !path.canonicalize().is_ok_and(|path| path.starts_with(root)) {
+            return Err(anyhow!(
+                "Failed to include `{}`, it isn't a file in {}",
+                include,
+                root.display()
+            ));
+        }
+
+        actions.extend(
+            read_actions(&path, root, contexts)
+                .with_context(|| format!("Failed to include `{}`", include))?,
+        );
+    }
+
+    Ok(actions)
+}
+
+pub(crate) fn is_snippet(action: &JsonValue) -> bool {
+    action.get("action").and_then(JsonValue::as_str) == Some("include")
+}
+
+fn expand_snippet(
+    action: &JsonValue,
+    root: &Path,
+    contexts: &Contexts,
+    depth: usize,
+) -> Result<Vec<JsonValue>> {
+    let include = SnippetInclude::deserialize(action).context("Invalid `action: include`")?;
+
+    // Snippets only see the arguments they're included with
+    let mut contexts = contexts.clone();
+    contexts.remove("args");
+    let contexts = with_values(&contexts, "args", include.with.iter());
+
+    let path = snippet_path(root, &include.snippet)?;
+    let actions = read_actions(&path, root, &contexts)
+        .with_context(|| format!("Failed to include snippet `{}`", include.snippet))?;
+
+    let mut actions = expand_actions(actions, root, &contexts, depth + 1)?;
+
+    if let Some(condition) = &include.condition {
+        actions
+            .iter_mut()
+            .for_each(|action| add_condition(action, condition));
+    }
+
+    Ok(actions)
+}
+
+/// Adds `condition` to the `where` of the action
+pub(crate) fn add_condition(action: &mut JsonValue, condition: &str) {
+    let Some(fields) = action.as_object_mut() else {
+        return;
+    };
+
+    let condition = match fields.get("where").and_then(JsonValue::as_str) {
+        Some(own) => format!("({}) && ({})", condition, own),
+        None => condition.to_string(),
+    };
+
+    fields.insert(String::from("where"), JsonValue::String(condition));
+}
+
+/// `snippets/<name>.yaml`, `.yml`, `.toml`, `.json` or `.json5` below the
+/// manifest root
+fn snippet_path(root: &Path, name: &str) -> Result<PathBuf> {
+    let snippets = root.join("snippets");
+
+    ["yaml", "yml", "toml", "json", "json5"]
+        .iter()
+        .map(|extension| snippets.join(format!("{}.{}", name, extension)))
+        .find(|path| path.is_file())
+        .filter(|path| {
+            path.canonicalize()
+                .is_ok_and(|path| path.starts_with(&snippets))
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Failed to include snippet `{}`, it isn't in {}",
+                name,
+                snippets.display()
+            )
+        })
+}
+
+/// Renders a file of actions, either a list of actions or a map with
+/// `actions`, the only way to write them in TOML
+pub(crate) fn read_actions(
+    path: &Path,
+    root: &Path,
+    contexts: &Contexts,
+) -> Result<Vec<JsonValue>> {
+    let contents = std::fs::read_to_string(path)?;
+    let rendered = render(Some(root), &contents, &to_tera(contexts))?;
+
+    let value: JsonValue = Format::of(path)
+        .unwrap_or(Format::Yaml)
+        .parse::<Option<JsonValue>>(&rendered)?
+        .unwrap_or_default();
+
+    match value {
+        JsonValue::Array(actions) => Ok(actions),
+        JsonValue::Object(mut fields) => match fields.remove("actions") {
+            Some(JsonValue::Array(actions)) => Ok(actions),
+            _ => Err(anyhow!("Expected a list of actions")),
+        },
+        JsonValue::Null => Ok(vec![]),
+        _ => Err(anyhow!("Expected a list of actions")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn it_can_expand_includes_and_snippets() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+
+        std::fs::create_dir_all(root.join("common"))?;
+        std::fs::create_dir_all(root.join("snippets").join("cli"))?;
+
+        std::fs::write(
+            root.join("common").join("base.yaml"),
+            "- action: command.run\n  command: echo\n  args: [\"{{ variables.ship }}\"]\n",
+        )?;
+        std::fs::write(
+            root.join("snippets").join("cli").join("github.yaml"),
+            r#"
+- action: binary.github
+  name: "{{ args.name }}"
+  directory: ~/.local/bin
+  repository: "{{ args.repo }}"
+- action: include
+  snippet: completions
+  with:
+    name: "{{ args.name }}"
+"#,
+        )?;
+        std::fs::write(
+            root.join("snippets").join("completions.toml"),
+            "[[actions]]\naction = \"command.run\"\ncommand = \"{{ args.name }}\"\nargs = [\"completions\"]\nwhere = \"os.name == \\\"linux\\\"\"\n",
+        )?;
+
+        let mut manifest = json!({
+            "include": ["common/base.yaml"],
+            "actions": [
+                {
+                    "action": "include",
+                    "snippet": "cli/github",
+                    "with": { "name": "k9s", "repo": "derailed/k9s" },
+                    "where": "user.username != \"root\""
+                }
+            ]
+        });
+
+        let mut contexts = Contexts::new();
+        contexts.insert(
+            String::from("variables"),
+            BTreeMap::from([(String::from("ship"), Value::from("Daedalus"))]),
+        );
+
+        expand(manifest.as_object_mut().unwrap(), &root, &contexts)?;
+
+        assert_eq!(
+            json!([
+                { "action": "command.run", "command": "echo", "args": ["Daedalus"] },
+                {
+                    "action": "binary.github",
+                    "name": "k9s",
+                    "directory": "~/.local/bin",
+                    "repository": "derailed/k9s",
+                    "where": "user.username != \"root\""
+                },
+                {
+                    "action": "command.run",
+                    "command": "k9s",
+                    "args": ["completions"],
+                    "where": "(user.username != \"root\") && (os.name == \"linux\")"
+                }
+            ]),
+            manifest["actions"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_only_includes_files_below_the_root() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+
+        let mut manifest = json!({ "include": ["../../etc/passwd"] });
+        assert!(expand(manifest.as_object_mut().unwrap(), &root, &Contexts::new()).is_err());
+
+        let mut manifest = json!({ "actions": [{ "action": "include", "snippet": "missing" }] });
+        assert!(expand(manifest.as_object_mut().unwrap(), &root, &Contexts::new()).is_err());
+
+        Ok(())
+    }
+}
@@ -1,122 +1,1079 @@
-use super::Manifest;
+use super::{
+    discovery::{self, walk},
+    format::Format,
+    Manifest,
+};
 use crate::{
-    contexts::{to_tera, Contexts},
-    manifests::get_manifest_name,
-    tera_functions::register_functions,
+    atoms::plugin::setup_globals,
+    config::DiscoveryConfig,
+    contexts::{to_tera, variables::define, Contexts},
+    manifests::{get_manifest_name, include, roles, with_manifest, with_vars},
+    tera_functions::render,
+    utilities::lua::lua_value_to_json,
+    values::Value,
 };
+use anyhow::{anyhow, Context};
 use ignore::WalkBuilder;
+use regex::Regex;
+use serde_json::{Map as JsonMap, Value as JsonValue};
 use std::{
-    collections::HashMap, error::Error, ffi::OsStr, fs::canonicalize, ops::Deref, path::PathBuf,
+    collections::{BTreeMap, HashMap},
+    fs::canonicalize,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock, PoisonError},
 };
-use tera::Tera;
-use tracing::{error, span};
-
-pub fn load(manifest_path: PathBuf, contexts: &Contexts) -> HashMap<String, Manifest> {
-    let mut manifests: HashMap<String, Manifest> = HashMap::new();
-
-    let mut walker = WalkBuilder::new(&manifest_path);
-
-    // FIXME: get rid of all .unwrap() calls
-    walker
-        .standard_filters(true)
-        .follow_links(false)
-        .same_file_system(true)
-        // Arbitrary for now, 9 "should" be enough?
-        .max_depth(Some(9))
-        .filter_entry(|entry| {
-            !(entry.file_type().is_some_and(|ft| ft.is_dir())
-            && entry.file_name() == OsStr::new("files"))
-        })
+use tealr::mlu::mlua::{Lua, Value as LuaValue};
+use tracing::{debug, error, span};
+
+/// The raw, unrendered contents of every file below `manifest_path`, which
+/// are its manifests, snippets, roles, templates and the files in `files/`
+/// directories, and of the remote roles they use
+pub fn sources(manifest_path: &Path) -> Vec<String> {
+    let root = match manifest_path.is_dir() {
+        true => manifest_path,
+        false => manifest_path.parent().unwrap_or(manifest_path),
+    };
+
+    let mut sources = read_tree(root);
+    let mut fetched: Vec<PathBuf> = vec![];
+
+    // Remote roles may use remote roles of their own
+    loop {
+        let directories: Vec<PathBuf> = roles::remote_role_directories(&sources)
+            .into_iter()
+            .filter(|directory| !fetched.contains(directory))
+            .collect();
+
+        if directories.is_empty() {
+            return sources;
+        }
+
+        for directory in directories {
+            sources.extend(read_tree(&directory));
+            fetched.push(directory);
+        }
+    }
+}
+
+/// The contents of the text files below `directory`, hidden ones included
+fn read_tree(directory: &Path) -> Vec<String> {
+    WalkBuilder::new(directory)
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
         .build()
-        // Don't walk directories
-        .filter(|entry| {
-            !entry
-                .as_ref()
-                .ok()
-                .and_then(|entry| entry.metadata().ok().map(|entry| entry.is_dir()))
-                .unwrap_or(false)
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .collect()
+}
+
+/// A manifest file that's been read, but not rendered yet
+#[derive(Clone, Debug)]
+pub struct ManifestSource {
+    pub name: String,
+    pub path: PathBuf,
+    pub contents: String,
+    /// The directory of all manifests, `include`s, snippets and roles are in it
+    pub root: PathBuf,
+    /// What a Lua manifest returned, by the contexts it was run with, so
+    /// it's run once for each of them
+    evaluated: Evaluations,
+}
+
+type Evaluations = Arc<Mutex<Vec<(Contexts, Result<JsonValue, String>)>>>;
+
+/// Every manifest file below `manifest_path`, except those other manifests
+/// `include` and those its index doesn't list. Fails when two files would be
+/// manifests of the same name.
+pub fn read(
+    manifest_path: &Path,
+    discovery: &DiscoveryConfig,
+) -> anyhow::Result<Vec<ManifestSource>> {
+    let root = match manifest_path.is_dir() {
+        true => manifest_path.to_path_buf(),
+        false => manifest_path
+            .parent()
+            .unwrap_or(manifest_path)
+            .to_path_buf(),
+    };
+    let root = canonicalize(&root).unwrap_or(root);
+
+    let index = match manifest_path.is_dir() {
+        true => discovery::index(manifest_path)?,
+        false => None,
+    };
+
+    let sources: Vec<ManifestSource> = walk(manifest_path, discovery)
+        .filter_map(Result::ok)
+        .filter(
+            |entry| match (&index, entry.path().strip_prefix(manifest_path)) {
+                (Some(globs), Ok(path)) => globs.is_match(path),
+                _ => true,
+            },
+        )
+        .filter_map(|entry| {
+            let path = canonicalize(entry.into_path()).ok()?;
+
+            let name = match get_manifest_name(manifest_path, &path) {
+                Ok(name) => name,
+                Err(err) => {
+                    error!(
+                        "Failed to get the name of manifest {}: {}",
+                        path.display(),
+                        err
+                    );
+                    return None;
+                }
+            };
+
+            let contents = std::fs::read_to_string(&path).unwrap_or_default();
+
+            Some(ManifestSource {
+                name,
+                path,
+                contents,
+                root: root.clone(),
+                evaluated: Default::default(),
+            })
         })
-        .filter(|entry| {
-            entry
-                .as_ref()
-                .ok()
-                .and_then(|entry| entry.file_name().to_str())
-                .map(|file_name| {
-                    file_name.ends_with(".yaml")
-                        || file_name.ends_with(".yml")
-                        || file_name.ends_with(".toml")
-                })
-                .unwrap_or(false)
+        .collect();
+
+    let included: Vec<PathBuf> = sources.iter().flat_map(ManifestSource::includes).collect();
+
+    let sources: Vec<ManifestSource> = sources
+        .into_iter()
+        .filter(|source| !included.contains(&source.path))
+        .collect();
+
+    let mut paths: HashMap<&str, &Path> = HashMap::new();
+
+    for source in &sources {
+        if let Some(path) = paths.insert(&source.name, &source.path) {
+            return Err(anyhow!(
+                "Manifests {} and {} are both named '{}'",
+                path.display(),
+                source.path.display(),
+                source.name
+            ));
+        }
+    }
+
+    Ok(sources)
+}
+
+/// Variables that aren't known before a manifest runs: its own `vars`, the
+/// manifest itself, and the outputs of its dependencies and its actions
+const DEFERRED: [&str; 4] = ["vars", "manifest", "deps", "registered"];
+
+fn is_deferred(name: &str) -> bool {
+    DEFERRED.iter().any(|prefix| is_below(name, prefix))
+}
+
+/// Whether the variable `name` is `prefix` or one of its fields
+pub(super) fn is_below(name: &str, prefix: &str) -> bool {
+    name.split('.').next() == Some(prefix)
+}
+
+impl ManifestSource {
+    pub(crate) fn format(&self) -> Format {
+        Format::of(&self.path).unwrap_or(Format::Yaml)
+    }
+
+    pub(crate) fn is_lua(&self) -> bool {
+        self.format() == Format::Lua
+    }
+
+    /// Runs a Lua manifest, which returns the table of the manifest. It has
+    /// the same `contexts` global as plugins. It's only run again for other
+    /// contexts.
+    pub(crate) fn evaluate(&self, contexts: &Contexts) -> anyhow::Result<JsonValue> {
+        let mut evaluated = self
+            .evaluated
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let result = match evaluated
+            .iter()
+            .find(|(evaluated, _)| evaluated == contexts)
+        {
+            Some((_, result)) => result.clone(),
+            None => {
+                let result = self.run(contexts).map_err(|err| err.to_string());
+                evaluated.push((contexts.clone(), result.clone()));
+
+                result
+            }
+        };
+
+        result.map_err(|err| anyhow!("{}", err))
+    }
+
+    fn run(&self, contexts: &Contexts) -> anyhow::Result<JsonValue> {
+        let lua = Lua::new();
+        setup_globals(&lua, contexts.clone())?;
+
+        let value: LuaValue = lua
+            .load(&self.contents)
+            .set_name(format!("@{}", self.path.display()))
+            .eval()
+            .map_err(|err| anyhow!("{}", err))?;
+
+        Ok(lua_to_manifest(value))
+    }
+
+    /// The whole manifest, rendered with `contexts` and an empty value for
+    /// each variable it doesn't have that `defers` accepts
+    fn fields(
+        &self,
+        contexts: &Contexts,
+        defers: impl Fn(&str) -> bool,
+    ) -> anyhow::Result<JsonMap<String, JsonValue>> {
+        let value = match self.is_lua() {
+            true => self.evaluate(contexts)?,
+            false => self
+                .format()
+                .parse::<Option<JsonValue>>(&render_leniently(
+                    &self.root,
+                    &self.contents,
+                    contexts,
+                    defers,
+                )?)?
+                .unwrap_or_default(),
+        };
+
+        match value {
+            JsonValue::Object(fields) => Ok(fields),
+            JsonValue::Null => Ok(Default::default()),
+            _ => Err(anyhow!("A manifest must be a map")),
+        }
+    }
+
+    /// The manifest as far as it's known before it runs, rendered with
+    /// `contexts` and empty `vars`, `manifest`, `deps` and `registered` where
+    /// `contexts` doesn't have them
+    pub(crate) fn outline(
+        &self,
+        contexts: &Contexts,
+    ) -> anyhow::Result<JsonMap<String, JsonValue>> {
+        self.fields(contexts, is_deferred)
+    }
+
+    /// Only what's needed to order the manifests, `depends`, `after` and `labels`,
+    /// rendered with the global contexts. The rest of the manifest may use
+    /// the outputs of its dependencies, so it's rendered right before it runs.
+    pub fn header(&self, contexts: &Contexts) -> anyhow::Result<Manifest> {
+        let mut fields = self.outline(contexts)?;
+
+        let header = ["depends", "after", "labels"]
+            .iter()
+            .filter_map(|key| fields.remove_entry(*key))
+            .collect();
+
+        self.with_name(serde_json::from_value(JsonValue::Object(header))?)
+    }
+
+    /// The files this manifest `include`s, as far as they can be known
+    /// before it's rendered
+    fn includes(&self) -> Vec<PathBuf> {
+        // Manifests are read before there are contexts, those that need
+        // them to know what they include have theirs loaded as manifests too
+        let includes = match self.fields(&Contexts::new(), |_| true) {
+            Ok(mut fields) => fields
+                .remove("include")
+                .and_then(|include| serde_json::from_value::<Vec<String>>(include).ok()),
+            Err(err) => {
+                debug!(
+                    "Manifest {} doesn't render without contexts, so its includes aren't known: {}",
+                    self.name, err
+                );
+                None
+            }
+        };
+
+        includes
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|include| canonicalize(self.root.join(include)).ok())
+            .collect()
+    }
+
+    /// Renders the manifest with `contexts` and its own `vars`. Its `outputs`
+    /// are only rendered once its actions ran, see `outputs`.
+    pub fn render(&self, contexts: &Contexts) -> anyhow::Result<Manifest> {
+        let contexts = self.contexts(contexts)?;
+        let mut fields = self.fields(&contexts, |name| is_below(name, "registered"))?;
+
+        include::expand(&mut fields, &self.root, &contexts)?;
+
+        self.with_name(serde_json::from_value(JsonValue::Object(fields))?)
+    }
+
+    /// The `outputs` of `manifest`, rendered once its actions ran, with
+    /// their output as `registered.<name>` in `contexts`
+    pub fn outputs(
+        &self,
+        manifest: &Manifest,
+        contexts: &Contexts,
+    ) -> anyhow::Result<BTreeMap<String, Value>> {
+        // Lua manifests aren't rendered, their outputs are templates
+        if self.is_lua() {
+            return manifest.render_outputs(contexts);
+        }
+
+        Ok(self
+            .render(contexts)?
+            .outputs
+            .into_iter()
+            .map(|(name, output)| (name, Value::from(output)))
+            .collect())
+    }
+
+    /// The contexts plus the manifest itself, from its header, and its own
+    /// `vars`. Lua manifests have variables of their own instead.
+    pub(crate) fn contexts(&self, contexts: &Contexts) -> anyhow::Result<Contexts> {
+        let contexts = with_manifest(contexts, &self.header(contexts)?);
+
+        if self.is_lua() {
+            return Ok(contexts);
+        }
+
+        let vars = self
+            .vars(&contexts)
+            .context("Failed to read the manifest's vars")?;
+
+        Ok(with_vars(&contexts, &vars))
+    }
+
+    /// The manifest rendered as for `outline`, before it's parsed
+    pub(crate) fn outline_str(&self, contexts: &Contexts) -> anyhow::Result<String> {
+        render_leniently(&self.root, &self.contents, contexts, is_deferred)
+    }
+
+    /// The rendered manifest, before it's parsed. `contexts` should include
+    /// the manifest's `vars`.
+    pub(crate) fn render_str(&self, contexts: &Contexts) -> anyhow::Result<String> {
+        render_leniently(&self.root, &self.contents, contexts, |name| {
+            is_below(name, "registered")
         })
-        .for_each(|entry| {
-            if let Ok(filename) = entry {
-                let span = span!(
-                    tracing::Level::INFO,
-                    "manifest_load",
-                    manifest = filename.file_name().to_str()
-                )
-                .entered();
-
-                let entry = canonicalize(filename.into_path()).ok().unwrap_or_default();
-                let contents =
-                    std::fs::read_to_string(entry.clone()).unwrap_or_else(|_| String::from(""));
-                let template = contents.as_str();
-
-                let mut tera = Tera::default();
-                register_functions(&mut tera);
-
-                let template = match tera.render_str(template, &to_tera(contexts)) {
-                    Ok(template) => template,
-                    Err(err) => {
-                        match err.source() {
-                            Some(err) => error!(message = err.source()),
-                            None => error!(message = err.to_string().as_str()),
-                        }
-
-                        span.exit();
-
-                        return;
-                    }
-                };
-
-                let manifest: anyhow::Result<Manifest> = match entry
-                    .extension()
-                    .and_then(OsStr::to_str)
-                {
-                    Some("yaml") | Some("yml") => {
-                        serde_yml::from_str::<Manifest>(template.deref()).map_err(anyhow::Error::from)
-                    }
-                    Some("toml") => toml::from_str::<Manifest>(template.deref()).map_err(anyhow::Error::from),
-                    _ => {
-                        error!("Unrecognized file extension for manifest");
-                        span.exit();
+    }
 
-                        return;
-                    }
-                };
+    /// A manifest's `vars` are read before the rest of it is rendered, so it
+    /// can use them. They're rendered with the whole manifest, without
+    /// `vars` of course.
+    fn vars(&self, contexts: &Contexts) -> anyhow::Result<BTreeMap<String, JsonValue>> {
+        match self.outline(contexts)?.remove("vars") {
+            Some(vars) => Ok(serde_json::from_value(vars)?),
+            None => Ok(BTreeMap::new()),
+        }
+    }
 
-                match manifest {
-                    Ok(mut manifest) => {
-                        let name = get_manifest_name(&manifest_path, &entry)
-                            .expect("Failed to get manifest name");
+    fn with_name(&self, mut manifest: Manifest) -> anyhow::Result<Manifest> {
+        manifest.root_dir = self.path.parent().map(|parent| parent.to_path_buf());
+        manifest.root = Some(self.root.clone());
+        manifest.name = Some(self.name.clone());
 
-                        manifest.root_dir = entry.parent().map(|parent| parent.to_path_buf());
+        Ok(manifest)
+    }
+}
 
-                        manifest.name = Some(name.clone());
+/// The value a Lua manifest returned. Tables with a sequence are arrays,
+/// other tables are objects. As a table can't hold `nil`, empty tables are
+/// `null`, so fields set to `{}` fall back to their defaults.
+fn lua_to_manifest(value: LuaValue) -> JsonValue {
+    match value {
+        LuaValue::Table(table) if table.raw_len() > 0 => JsonValue::Array(
+            table
+                .sequence_values::<LuaValue>()
+                .filter_map(Result::ok)
+                .map(lua_to_manifest)
+                .collect(),
+        ),
+        LuaValue::Table(table) => {
+            let fields: JsonMap<String, JsonValue> = table
+                .pairs::<LuaValue, LuaValue>()
+                .filter_map(Result::ok)
+                .filter_map(|(key, value)| {
+                    let key = match key {
+                        LuaValue::String(key) => key.to_string_lossy(),
+                        LuaValue::Integer(key) => key.to_string(),
+                        _ => return None,
+                    };
 
-                        manifests.insert(name, manifest);
+                    match lua_to_manifest(value) {
+                        JsonValue::Null => None,
+                        value => Some((key, value)),
                     }
-                    Err(err) => {
-                        let manifest_name =
-                            get_manifest_name(&manifest_path, &entry).unwrap_or_default();
+                })
+                .collect();
 
-                        error!("Manifest '{manifest_name}' in file with path '{}' cannot be parsed. Reason: {err}", &entry.display());
-                    }
+            match fields.is_empty() {
+                true => JsonValue::Null,
+                false => JsonValue::Object(fields),
+            }
+        }
+        value => lua_value_to_json(value),
+    }
+}
+
+pub fn load(
+    manifest_path: PathBuf,
+    discovery: &DiscoveryConfig,
+    contexts: &Contexts,
+) -> anyhow::Result<HashMap<String, Manifest>> {
+    Ok(read(&manifest_path, discovery)?
+        .into_iter()
+        .filter_map(|source| {
+            let _span = span!(
+                tracing::Level::INFO,
+                "manifest_load",
+                manifest = source.name.as_str()
+            )
+            .entered();
+
+            match source.render(contexts) {
+                Ok(manifest) => Some((source.name, manifest)),
+                Err(err) => {
+                    error!(
+                        "Manifest '{}' in file with path '{}' cannot be parsed. Reason: {:#}",
+                        source.name,
+                        source.path.display(),
+                        err
+                    );
+
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+/// Renders `template` like `render`, but with an empty string for every
+/// variable it uses that `contexts` doesn't have and `defers` accepts, e.g.
+/// the `inputs` of a role before they're known
+pub(super) fn render_leniently(
+    root: &Path,
+    template: &str,
+    contexts: &Contexts,
+    defers: impl Fn(&str) -> bool,
+) -> anyhow::Result<String> {
+    let mut contexts = contexts.clone();
+    let mut deferred: Vec<String> = vec![];
+
+    loop {
+        let err = match render(Some(root), template, &to_tera(&contexts)) {
+            Ok(rendered) => return Ok(rendered),
+            Err(err) => err,
+        };
+
+        // Each variable is only given a value once, in case it's not enough
+        match missing_variable(&err) {
+            Some(name) if defers(&name) && !deferred.contains(&name) => {
+                let (prefix, key) = name.split_once('.').unwrap_or((&name, ""));
+                let values = contexts.entry(prefix.to_string()).or_default();
+
+                if !key.is_empty() {
+                    define(values, key, Value::from(""));
                 }
 
-                span.exit();
+                deferred.push(name);
             }
-        });
+            _ => return Err(err.into()),
+        }
+    }
+}
+
+/// The variable Tera failed to find, if that's why it failed to render
+fn missing_variable(err: &tera::Error) -> Option<String> {
+    static MISSING: OnceLock<Regex> = OnceLock::new();
+
+    let missing = MISSING.get_or_init(|| {
+        Regex::new(r"^Variable `([^`\[]+)[^`]*` not found in context")
+            .expect("Failed to compile the missing variable regex")
+    });
+
+    let mut source: Option<&dyn std::error::Error> = Some(err);
+
+    while let Some(err) = source {
+        if let Some(captures) = missing.captures(&err.to_string()) {
+            return Some(captures[1].to_string());
+        }
+
+        source = err.source();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{tera_functions::TEMPLATES_DIR, values::Value};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_render_manifests_with_vars() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+
+        std::fs::write(
+            manifest_path.path().join("sgc.yaml"),
+            r#"
+vars:
+  base: "{{ variables.planet }}"
+  gate: Milky Way
+
+env:
+  STARGATE: "{{ vars.gate }}"
+
+actions:
+{% for team in ["SG-1", "SG-2"] %}
+  - action: command.run
+    command: echo
+    args: ["{{ team }} from {{ vars.base }}"]
+{% endfor %}
+"#,
+        )?;
+
+        let mut contexts = Contexts::new();
+        contexts.insert(
+            String::from("variables"),
+            BTreeMap::from([(String::from("planet"), Value::from("Earth"))]),
+        );
+
+        let manifests = load(
+            manifest_path.path().to_path_buf(),
+            &DiscoveryConfig::default(),
+            &contexts,
+        )?;
+        let manifest = manifests.get("sgc").expect("Manifest wasn't loaded");
+
+        assert_eq!(2, manifest.actions.len());
+        assert_eq!(
+            Some(&String::from("Milky Way")),
+            manifest.env.get("STARGATE")
+        );
+        assert_eq!(
+            Some(&Value::from("Earth")),
+            crate::contexts::get(&manifest.contexts(&contexts), "vars.base")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_render_the_manifest_in_its_templates() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+        let root = manifest_path.path().canonicalize()?;
+        std::fs::create_dir_all(root.join("dev"))?;
+
+        std::fs::write(
+            root.join("dev").join("git.yaml"),
+            r#"
+labels: ["dev"]
+
+vars:
+  header: "Managed by {{ manifest.name }}"
+
+actions:
+  - action: command.run
+    command: echo
+    args: ["{{ vars.header }}", "{{ manifest.labels.0 }}", "{{ manifest.files_dir }}"]
+"#,
+        )?;
+
+        let manifests = load(root.clone(), &DiscoveryConfig::default(), &Contexts::new())?;
+        let manifest = manifests.get("dev.git").expect("Manifest wasn't loaded");
+
+        let args = match &manifest.actions[0] {
+            crate::actions::Actions::CommandRun(run) => run.action.args.clone(),
+            _ => panic!("Expected a command.run"),
+        };
+
+        assert_eq!(
+            vec![
+                String::from("Managed by dev.git"),
+                String::from("dev"),
+                root.join("dev").join("files").display().to_string(),
+            ],
+            args
+        );
+        assert_eq!(
+            Some(&Value::from("dev.git")),
+            crate::contexts::get(&manifest.contexts(&Contexts::new()), "manifest.name")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_renders_outputs_after_the_actions_ran() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+
+        std::fs::write(
+            manifest_path.path().join("go-tools.yaml"),
+            r#"
+depends:
+  - toolchain
+labels: ["{{ variables.label }}"]
+
+actions:
+  - action: command.run
+    command: go
+    args: ["install", "gopls@{{ deps.toolchain.version }}"]
+    register: gopls
+
+outputs:
+  gopls: "{{ registered.gopls | trim }} for Go {{ deps.toolchain.version }}"
+"#,
+        )?;
+
+        let mut contexts = Contexts::new();
+        contexts.insert(
+            String::from("variables"),
+            BTreeMap::from([(String::from("label"), Value::from("dev"))]),
+        );
+
+        let source = read(manifest_path.path(), &DiscoveryConfig::default())?
+            .pop()
+            .expect("Manifest wasn't read");
+
+        // Dependencies haven't run yet, so only the header can be rendered
+        let header = source.header(&contexts)?;
+        assert_eq!(vec![String::from("toolchain")], header.depends);
+        assert_eq!(vec![String::from("dev")], header.labels);
+        assert!(source.render(&contexts).is_err());
+
+        let deps = BTreeMap::from([(
+            String::from("toolchain"),
+            BTreeMap::from([(String::from("version"), Value::from("1.22.3"))]),
+        )]);
+
+        let manifest = source.render(&crate::manifests::with_deps(&contexts, &deps))?;
+        assert_eq!(1, manifest.actions.len());
+        assert_eq!(Some("gopls"), manifest.actions[0].inner_ref().register());
+
+        let mut contexts = crate::manifests::with_deps(&contexts, &deps);
+        contexts.insert(
+            String::from("registered"),
+            BTreeMap::from([(String::from("gopls"), Value::from("v0.15.3\n"))]),
+        );
+
+        assert_eq!(
+            BTreeMap::from([(String::from("gopls"), Value::from("v0.15.3 for Go 1.22.3"))]),
+            source.outputs(&manifest, &contexts)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_render_templated_headers_and_vars() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+
+        std::fs::write(
+            manifest_path.path().join("stargate.yaml"),
+            r#"
+depends:
+{% if variables.planet == "Earth" %}  - earth{% endif %}
+{% if variables.planet == "Abydos" %}after: [abydos]{% else %}after: [dakara]{% endif %}
+
+{% if variables.planet == "Earth" %}
+vars:
+  base: SGC
+{% endif %}
+
+actions:
+  - action: command.run
+    command: echo
+    args: ["{{ vars.base }}"]
+"#,
+        )?;
+
+        let mut contexts = Contexts::new();
+        contexts.insert(
+            String::from("variables"),
+            BTreeMap::from([(String::from("planet"), Value::from("Earth"))]),
+        );
+
+        let source = read(manifest_path.path(), &DiscoveryConfig::default())?
+            .pop()
+            .expect("Manifest wasn't read");
+
+        let header = source.header(&contexts)?;
+        assert_eq!(vec![String::from("earth")], header.depends);
+        assert_eq!(vec![String::from("dakara")], header.after);
+
+        match &source.render(&contexts)?.actions[..] {
+            [crate::actions::Actions::CommandRun(run)] => {
+                assert_eq!(vec![String::from("SGC")], run.action.args)
+            }
+            _ => panic!("did not get a command to run"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_load_json_manifests() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+
+        std::fs::write(
+            manifest_path.path().join("sgc.json"),
+            r#"{
+  "vars": { "base": "{{ variables.planet }}" },
+  "depends": ["earth"],
+  "outputs": { "team": "{{ registered.team }}" },
+  "actions": [
+    { "action": "command.run", "command": "echo", "args": ["SG-1 from {{ vars.base }}"] }
+  ]
+}"#,
+        )?;
+        std::fs::write(
+            manifest_path.path().join("earth.json5"),
+            "{\n  // Generated\n  actions: [{ action: 'command.run', command: 'echo', }],\n}\n",
+        )?;
+
+        let mut contexts = Contexts::new();
+        contexts.insert(
+            String::from("variables"),
+            BTreeMap::from([(String::from("planet"), Value::from("Earth"))]),
+        );
+
+        let sources = read(manifest_path.path(), &DiscoveryConfig::default())?;
+        let sgc = sources.iter().find(|source| source.name == "sgc").unwrap();
+        assert_eq!(vec![String::from("earth")], sgc.header(&contexts)?.depends);
+
+        let manifests = load(
+            manifest_path.path().to_path_buf(),
+            &DiscoveryConfig::default(),
+            &contexts,
+        )?;
+        let mut names: Vec<&String> = manifests.keys().collect();
+        names.sort();
+        assert_eq!(vec!["earth", "sgc"], names);
+
+        let manifest = &manifests["sgc"];
+
+        let mut registered = contexts.clone();
+        registered.insert(
+            String::from("registered"),
+            BTreeMap::from([(String::from("team"), Value::from("SG-1"))]),
+        );
+        assert_eq!(
+            BTreeMap::from([(String::from("team"), Value::from("SG-1"))]),
+            sgc.outputs(manifest, &registered)?
+        );
+
+        match &manifest.actions[..] {
+            [crate::actions::Actions::CommandRun(run)] => {
+                assert_eq!(vec![String::from("SG-1 from Earth")], run.action.args)
+            }
+            _ => panic!("did not get a command to run"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_fails_when_manifests_have_the_same_name() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+
+        std::fs::write(manifest_path.path().join("sgc.yaml"), "actions: []\n")?;
+        std::fs::write(manifest_path.path().join("sgc.toml"), "actions = []\n")?;
+
+        let err = read(manifest_path.path(), &DiscoveryConfig::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.ends_with("are both named 'sgc'"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_load_lua_manifests() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+
+        std::fs::write(
+            manifest_path.path().join("sgc.lua"),
+            r#"
+local actions = {}
+
+for _, team in ipairs({ "SG-1", "SG-2" }) do
+  table.insert(actions, {
+    action = "command.run",
+    command = "echo",
+    args = { team .. " from " .. contexts.variables.planet },
+  })
+end
+
+return {
+  depends = { "earth" },
+  labels = {},
+  actions = actions,
+}
+"#,
+        )?;
+
+        let mut contexts = Contexts::new();
+        contexts.insert(
+            String::from("variables"),
+            BTreeMap::from([(String::from("planet"), Value::from("Earth"))]),
+        );
+
+        let sources = read(manifest_path.path(), &DiscoveryConfig::default())?;
+        assert_eq!(
+            vec![String::from("earth")],
+            sources[0].header(&contexts)?.depends
+        );
+
+        let manifests = load(
+            manifest_path.path().to_path_buf(),
+            &DiscoveryConfig::default(),
+            &contexts,
+        )?;
+        let manifest = manifests.get("sgc").expect("Manifest wasn't loaded");
+
+        let args: Vec<Vec<String>> = manifest
+            .actions
+            .iter()
+            .map(|action| match action {
+                crate::actions::Actions::CommandRun(run) => run.action.args.clone(),
+                _ => panic!("did not get a command to run"),
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                vec![String::from("SG-1 from Earth")],
+                vec![String::from("SG-2 from Earth")]
+            ],
+            args
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_lua_manifests_once_for_each_contexts() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+        let runs = manifest_path.path().join("runs");
+
+        std::fs::write(
+            manifest_path.path().join("sgc.lua"),
+            format!(
+                r#"
+local runs = io.open("{}", "a")
+runs:write("run\n")
+runs:close()
+
+return {{ labels = {{ "stargate" }}, actions = {{}} }}
+"#,
+                runs.display()
+            ),
+        )?;
+
+        let source = read(manifest_path.path(), &DiscoveryConfig::default())?.remove(0);
+        let contexts = Contexts::new();
+
+        source.header(&contexts)?;
+        source.contexts(&contexts)?;
+        source.render(&contexts)?;
+        source.render(&contexts)?;
+
+        // Once without the manifest, to find its header, and once with it
+        assert_eq!(2, std::fs::read_to_string(&runs)?.lines().count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_use_shared_templates() -> anyhow::Result<()> {
+        use crate::actions::Action;
+
+        let manifest_path = tempfile::tempdir()?;
+        let root = manifest_path.path();
+        let templates = root.join(TEMPLATES_DIR);
+
+        std::fs::create_dir_all(&templates)?;
+        std::fs::create_dir_all(root.join("shell").join("files"))?;
+
+        std::fs::write(
+            templates.join("macros.tera"),
+            "{% macro echo(text) %}{ action: command.run, command: echo, args: [\"{{ text }}\"] }{% endmacro %}",
+        )?;
+        std::fs::write(templates.join("header.sh"), "# Managed by comtrya")?;
+        std::fs::write(
+            root.join("shell").join("files").join("bashrc"),
+            "{% include \"header.sh\" %}\nexport EDITOR=nvim\n",
+        )?;
+        std::fs::write(
+            root.join("shell").join("bash.yaml"),
+            r#"{% import "macros.tera" as macros %}
+actions:
+  - {{ macros::echo(text="hi") }}
+  - action: file.copy
+    from: bashrc
+    to: /tmp/.bashrc
+    template: true
+"#,
+        )?;
+
+        let manifests = load(
+            root.to_path_buf(),
+            &DiscoveryConfig::default(),
+            &Contexts::new(),
+        )?;
+
+        // Templates aren't manifests
+        assert_eq!(
+            vec!["shell.bash"],
+            manifests.keys().collect::<Vec<&String>>()
+        );
+
+        let manifest = &manifests["shell.bash"];
+
+        match &manifest.actions[..] {
+            [crate::actions::Actions::CommandRun(run), crate::actions::Actions::FileCopy(copy)] => {
+                assert_eq!(vec![String::from("hi")], run.action.args);
+                assert!(copy.plan(manifest, &Contexts::new()).is_ok());
+            }
+            _ => panic!("did not get a command and a copy"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_load_manifests_with_roles() -> anyhow::Result<()> {
+        use crate::actions::Action;
+
+        let manifest_path = tempfile::tempdir()?;
+        let root = manifest_path.path();
+        let role = root.join("roles").join("nvim");
+
+        std::fs::create_dir_all(role.join("files"))?;
+        std::fs::write(
+            role.join("files").join("init.lua"),
+            "-- {{ inputs.leader }}",
+        )?;
+        std::fs::write(
+            role.join("role.yaml"),
+            r#"
+inputs:
+  leader:
+    type: string
+    default: ","
+actions:
+  - action: file.copy
+    from: init.lua
+    to: "{{ inputs.leader }}/init.lua"
+    template: true
+    where: inputs.leader != ""
+"#,
+        )?;
+        std::fs::write(
+            root.join("editor.yaml"),
+            "actions:\n  - action: role\n    name: nvim\n    inputs:\n      leader: space\n",
+        )?;
+
+        let manifests = load(
+            root.to_path_buf(),
+            &DiscoveryConfig::default(),
+            &Contexts::new(),
+        )?;
+
+        // The role isn't a manifest itself
+        assert_eq!(vec!["editor"], manifests.keys().collect::<Vec<&String>>());
+
+        let manifest = &manifests["editor"];
+        let copy = match &manifest.actions[..] {
+            [crate::actions::Actions::FileCopy(copy)] => copy,
+            _ => panic!("did not get the file.copy of the role"),
+        };
+
+        assert_eq!("space/init.lua", copy.action.to);
+
+        // The role's files and inputs are used when planning
+        let steps = copy.plan(manifest, &Contexts::new())?;
+        assert!(!steps.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_load_manifests_with_includes_and_snippets() -> anyhow::Result<()> {
+        let manifest_path = tempfile::tempdir()?;
+        let root = manifest_path.path();
+
+        std::fs::create_dir_all(root.join("common"))?;
+        std::fs::create_dir_all(root.join("snippets"))?;
+
+        std::fs::write(
+            root.join("common").join("base.yaml"),
+            "actions:\n  - action: command.run\n    command: echo\n",
+        )?;
+        std::fs::write(
+            root.join("snippets").join("github-cli.yaml"),
+            "- action: command.run\n  command: \"{{ args.name }}\"\n  args: [\"{{ vars.flag }}\"]\n",
+        )?;
+        std::fs::write(
+            root.join("k9s.yaml"),
+            r#"
+vars:
+  flag: --version
+include:
+  - common/base.yaml
+actions:
+  - action: include
+    snippet: github-cli
+    with:
+      name: k9s
+"#,
+        )?;
+
+        let manifests = load(
+            root.to_path_buf(),
+            &DiscoveryConfig::default(),
+            &Contexts::new(),
+        )?;
+
+        // Neither the included file nor the snippet are manifests themselves
+        assert_eq!(vec!["k9s"], manifests.keys().collect::<Vec<&String>>());
+
+        let commands: Vec<(String, Vec<String>)> = manifests["k9s"]
+            .actions
+            .iter()
+            .map(|action| match action {
+                crate::actions::Actions::CommandRun(run) => {
+                    (run.action.command.clone(), run.action.args.clone())
+                }
+                _ => panic!("did not get a command to run"),
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                (String::from("echo"), vec![]),
+                (String::from("k9s"), vec![String::from("--version")])
+            ],
+            commands
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reads_the_sources_of_files_too() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let files = root.path().join("shell").join("files").join("zsh");
+        std::fs::create_dir_all(&files)?;
+        std::fs::write(root.path().join("shell").join("main.yaml"), "actions: []")?;
+        std::fs::write(files.join(".zshrc"), "export IP={{ network.ip }}")?;
+
+        let sources = sources(root.path());
+
+        assert!(sources.contains(&String::from("actions: []")));
+        assert!(sources.contains(&String::from("export IP={{ network.ip }}")));
 
-    manifests
+        Ok(())
+    }
 }
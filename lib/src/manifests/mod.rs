@@ -1,14 +1,32 @@
+mod dependencies;
+mod discovery;
+mod format;
+use format::Format;
+mod include;
+pub use dependencies::{resolve_dependency, Dependencies};
 mod load;
-pub use load::load;
+pub use load::{load, read, sources, ManifestSource};
 mod providers;
+mod roles;
+mod validate;
 use crate::actions::Actions;
-use petgraph::prelude::*;
+use crate::contexts::{to_tera, variables::define, Contexts};
+use crate::tera_functions::render;
+use crate::values::Value;
+use anyhow::Context;
+use petgraph::prelude::NodeIndex;
 pub use providers::register_providers;
 pub use providers::ManifestProvider;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
-use tracing::error;
+use serde_json::Value as JsonValue;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+use tracing::{error, warn};
+pub use validate::{validate, Diagnostic};
 
 #[derive(JsonSchema, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Manifest {
@@ -21,35 +39,190 @@ pub struct Manifest {
     #[serde(default)]
     pub labels: Vec<String>,
 
+    /// Manifests to run first, pulled in when this one runs. Relative names
+    /// (`./`, `../`), globs and optional `?name` dependencies are supported.
     #[serde(default)]
     pub depends: Vec<String>,
 
+    /// Manifests to run first, but only when they're run anyway
+    #[serde(default)]
+    pub after: Vec<String>,
+
+    /// Variables for this manifest only, available as `vars.<name>` in its
+    /// templates, `where` conditions and templated files
+    #[serde(default)]
+    pub vars: BTreeMap<String, JsonValue>,
+
+    /// Environment variables for every command this manifest runs
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// Values published to the manifests depending on this one, as
+    /// `deps.<name>.<output>`. They're rendered once the actions ran, with
+    /// the output of actions as `registered.<name>`.
+    #[serde(default)]
+    pub outputs: BTreeMap<String, String>,
+
+    /// Files below the manifest root whose actions run before this
+    /// manifest's own. Use `action: include` for snippets.
+    #[serde(default)]
+    pub include: Vec<String>,
+
     #[serde(default)]
     pub actions: Vec<Actions>,
 
     #[serde(skip)]
     pub root_dir: Option<PathBuf>,
 
+    /// The root of the manifests, its `templates/` are shared by the
+    /// templates of every manifest and the files they copy
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+
+    #[deprecated(note = "Manifests are ordered by `Dependencies` now, this is never set")]
     #[serde(skip)]
     pub dag_index: Option<NodeIndex<u32>>,
 }
 
-pub fn resolve(uri: &String) -> Option<PathBuf> {
-    let manifest_directory = register_providers()
-        .into_iter()
-        .filter(|provider| std::ops::Deref::deref(&provider).looks_familiar(uri))
-        .fold(None, |path, provider| {
-            if path.is_some() {
-                return path;
+impl Manifest {
+    /// The contexts this manifest's actions are planned with, the global
+    /// contexts plus the manifest itself and its `vars`
+    pub fn contexts(&self, contexts: &Contexts) -> Contexts {
+        with_vars(&with_manifest(contexts, self), &self.vars)
+    }
+
+    /// The `files/` directory next to the manifest, where actions find the
+    /// files they copy and link
+    pub fn files_dir(&self) -> Option<PathBuf> {
+        self.root_dir
+            .as_ref()
+            .map(|root_dir| root_dir.join("files"))
+    }
+
+    /// What templates and `where` conditions see as `manifest`
+    fn metadata(&self) -> BTreeMap<String, JsonValue> {
+        let path = |path: Option<PathBuf>| match path {
+            Some(path) => JsonValue::String(path.display().to_string()),
+            None => JsonValue::Null,
+        };
+
+        BTreeMap::from([
+            (
+                String::from("name"),
+                self.name.clone().map_or(JsonValue::Null, JsonValue::String),
+            ),
+            (String::from("root_dir"), path(self.root_dir.clone())),
+            (String::from("labels"), JsonValue::from(self.labels.clone())),
+            (String::from("files_dir"), path(self.files_dir())),
+        ])
+    }
+
+    /// Exports the manifest's `env` to comtrya's own environment, so every
+    /// command its actions run sees it, be it a `command.run`, a package
+    /// manager or a plugin. The variables are put back as they were when
+    /// the returned `ExportedEnv` is dropped.
+    pub fn export_env(&self) -> ExportedEnv {
+        let previous = self
+            .env
+            .iter()
+            .map(|(key, value)| {
+                let previous = std::env::var_os(key);
+                std::env::set_var(key, value);
+
+                (key.clone(), previous)
+            })
+            .collect();
+
+        ExportedEnv(previous)
+    }
+
+    /// Renders the `outputs` with the contexts the actions ran with
+    pub fn render_outputs(&self, contexts: &Contexts) -> anyhow::Result<BTreeMap<String, Value>> {
+        let context = to_tera(contexts);
+
+        self.outputs
+            .iter()
+            .map(|(name, template)| {
+                let value = render(self.root.as_deref(), template, &context)
+                    .with_context(|| format!("Failed to render output '{}'", name))?;
+
+                Ok((name.clone(), Value::from(value)))
+            })
+            .collect()
+    }
+}
+
+/// The environment variables `Manifest::export_env` replaced, with the
+/// values they had before
+pub struct ExportedEnv(Vec<(String, Option<OsString>)>);
+
+impl Drop for ExportedEnv {
+    fn drop(&mut self) {
+        for (key, previous) in self.0.drain(..) {
+            match previous {
+                Some(value) => std::env::set_var(key, value),
+                None => std::env::remove_var(key),
             }
+        }
+    }
+}
 
-            match provider.resolve(uri.as_str()) {
-                Ok(path) => Some(path),
-                Err(_) => None,
+/// The global contexts plus the outputs of dependencies, by manifest name.
+/// Dotted names are nested, so `deps.dev.toolchain.go_version` works.
+pub fn with_deps(
+    contexts: &Contexts,
+    outputs: &BTreeMap<String, BTreeMap<String, Value>>,
+) -> Contexts {
+    let mut contexts = contexts.clone();
+    let mut deps = BTreeMap::new();
+
+    for (name, outputs) in outputs {
+        define(&mut deps, name, Value::Map(outputs.clone()));
+    }
+
+    contexts.insert(String::from("deps"), deps);
+
+    contexts
+}
+
+/// The contexts plus `manifest`, the name, directory, labels and files
+/// directory of `manifest`
+pub(crate) fn with_manifest(contexts: &Contexts, manifest: &Manifest) -> Contexts {
+    with_values(contexts, "manifest", manifest.metadata().iter())
+}
+
+pub(crate) fn with_vars(contexts: &Contexts, vars: &BTreeMap<String, JsonValue>) -> Contexts {
+    with_values(contexts, "vars", vars.iter())
+}
+
+/// The contexts plus `values` under `prefix`, e.g. a manifest's `vars` or
+/// the `args` a snippet is included with
+pub(crate) fn with_values<'a>(
+    contexts: &Contexts,
+    prefix: &str,
+    values: impl Iterator<Item = (&'a String, &'a JsonValue)>,
+) -> Contexts {
+    let mut contexts = contexts.clone();
+
+    let values: BTreeMap<String, Value> = values
+        .filter_map(|(key, value)| match Value::try_from(value.clone()) {
+            Ok(value) => Some((key.clone(), value)),
+            Err(error) => {
+                warn!("Ignoring {} '{}': {}", prefix, key, error);
+                None
             }
-        });
+        })
+        .collect();
+
+    if !values.is_empty() {
+        contexts.insert(prefix.to_string(), values);
+    }
+
+    contexts
+}
 
-    let manifest_directory = match manifest_directory {
+pub fn resolve(uri: &String) -> Option<PathBuf> {
+    let manifest_directory = match locate(uri) {
         Some(dir) => dir.canonicalize().expect("Failed to canonicalize path"),
         None => {
             error!("Failed to find manifests at {}", &uri);
@@ -60,6 +233,14 @@ pub fn resolve(uri: &String) -> Option<PathBuf> {
     Some(manifest_directory)
 }
 
+/// Like `resolve`, but gives up quietly when no provider can resolve the uri
+pub fn locate(uri: &str) -> Option<PathBuf> {
+    register_providers()
+        .into_iter()
+        .filter(|provider| std::ops::Deref::deref(&provider).looks_familiar(uri))
+        .find_map(|provider| provider.resolve(uri).ok())
+}
+
 pub fn get_manifest_name(manifest_directory: &Path, location: &Path) -> anyhow::Result<String> {
     let local_name = location.strip_prefix(manifest_directory)?;
     let manifest_name = local_name
@@ -78,8 +259,10 @@ pub fn get_manifest_name(manifest_directory: &Path, location: &Path) -> anyhow::
             s
         });
 
-    let manifest_name = manifest_name.trim_end_matches(".yaml");
-    let manifest_name = manifest_name.trim_end_matches(".yml");
+    let manifest_name = Format::EXTENSIONS
+        .iter()
+        .find_map(|extension| manifest_name.strip_suffix(&format!(".{}", extension)))
+        .unwrap_or(&manifest_name);
 
     Ok(String::from(manifest_name.trim_end_matches(".main")))
 }
@@ -144,6 +327,20 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_other_formats() {
+        let manifest_directory = PathBuf::from("/tmp");
+
+        for extension in ["toml", "json", "json5", "lua"] {
+            let location = PathBuf::from(format!("/tmp/test/hello.{}", extension));
+
+            assert_eq!(
+                "test.hello",
+                get_manifest_name(&manifest_directory, &location).unwrap()
+            );
+        }
+    }
+
     #[test]
     fn test_non_main_nested_yaml() {
         let manifest_directory = PathBuf::from("/tmp");
@@ -154,6 +351,46 @@ mod test {
             get_manifest_name(&manifest_directory, &location).unwrap()
         );
     }
+
+    #[test]
+    fn it_exports_the_env_until_dropped() {
+        std::env::set_var("COMTRYA_MANIFEST_SHIP", "Prometheus");
+
+        let manifest = Manifest {
+            env: HashMap::from([
+                (
+                    String::from("COMTRYA_MANIFEST_SHIP"),
+                    String::from("Daedalus"),
+                ),
+                (
+                    String::from("COMTRYA_MANIFEST_GATE"),
+                    String::from("Milky Way"),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        let exported = manifest.export_env();
+
+        assert_eq!(
+            Ok("Daedalus"),
+            std::env::var("COMTRYA_MANIFEST_SHIP").as_deref()
+        );
+        assert_eq!(
+            Ok("Milky Way"),
+            std::env::var("COMTRYA_MANIFEST_GATE").as_deref()
+        );
+
+        drop(exported);
+
+        assert_eq!(
+            Ok("Prometheus"),
+            std::env::var("COMTRYA_MANIFEST_SHIP").as_deref()
+        );
+        assert!(std::env::var("COMTRYA_MANIFEST_GATE").is_err());
+
+        std::env::remove_var("COMTRYA_MANIFEST_SHIP");
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,524 @@
+use super::{
+    include,
+    load::{is_below, render_leniently},
+    locate, with_values,
+};
+use crate::actions::{ActionRole, ROLE_FIELD};
+use crate::contexts::{to_tera, Contexts};
+use crate::tera_functions::render;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// An input a role declares in its `role.yaml`
+#[derive(Debug, Deserialize)]
+struct RoleInput {
+    #[serde(default, rename = "type")]
+    kind: InputType,
+
+    /// Used when the input isn't given, inputs without one are required
+    default: Option<JsonValue>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum InputType {
+    #[default]
+    Any,
+    String,
+    Number,
+    #[serde(alias = "bool")]
+    Boolean,
+    List,
+    Map,
+}
+
+impl InputType {
+    fn accepts(&self, value: &JsonValue) -> bool {
+        match self {
+            InputType::Any => true,
+            InputType::String => value.is_string(),
+            InputType::Number => value.is_number(),
+            InputType::Boolean => value.is_boolean(),
+            InputType::List => value.is_array(),
+            InputType::Map => value.is_object(),
+        }
+    }
+}
+
+impl Display for InputType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            InputType::Any => "any",
+            InputType::String => "string",
+            InputType::Number => "number",
+            InputType::Boolean => "boolean",
+            InputType::List => "list",
+            InputType::Map => "map",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Deserialize)]
+struct RoleInputs {
+    #[serde(default)]
+    inputs: BTreeMap<String, RoleInput>,
+}
+
+/// An `action: role`, which is replaced by the actions of the role
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RoleAction {
+    /// Always `role`
+    #[allow(dead_code)]
+    action: String,
+
+    name: String,
+
+    /// Where to find the role, a directory or a git repository, instead of
+    /// the `roles/` directory of the manifests
+    source: Option<String>,
+
+    #[serde(default)]
+    inputs: JsonMap<String, JsonValue>,
+
+    /// Added to the `where` of every action of the role
+    #[serde(rename = "where")]
+    condition: Option<String>,
+}
+
+pub(crate) fn is_role(action: &JsonValue) -> bool {
+    action.get("action").and_then(JsonValue::as_str) == Some("role")
+}
+
+pub(crate) fn expand_role(
+    action: &JsonValue,
+    root: &Path,
+    contexts: &Contexts,
+    depth: usize,
+) -> Result<Vec<JsonValue>> {
+    let role = RoleAction::deserialize(action).context("Invalid `action: role`")?;
+    let directory = role_directory(root, &role.name, role.source.as_deref())?;
+
+    let contents = std::fs::read_to_string(directory.join("role.yaml"))
+        .with_context(|| format!("Failed to read role `{}`", role.name))?;
+
+    // Roles only see the global contexts and their inputs
+    let mut contexts = contexts.clone();
+    for prefix in ["vars", "args", "inputs"] {
+        contexts.remove(prefix);
+    }
+
+    // Roles have their own files and templates
+    let declared: RoleInputs = serde_yml::from_str::<Option<_>>(&render_leniently(
+        &directory,
+        &contents,
+        &contexts,
+        |name| is_below(name, "inputs"),
+    )?)
+    .with_context(|| format!("Failed to parse role `{}`", role.name))?
+    .unwrap_or(RoleInputs {
+        inputs: BTreeMap::new(),
+    });
+
+    let inputs = resolve_inputs(&role.name, &declared.inputs, &role.inputs)?;
+    let contexts = with_values(&contexts, "inputs", inputs.iter());
+
+    let rendered = render(Some(&directory), &contents, &to_tera(&contexts))
+        .with_context(|| format!("Failed to render role `{}`", role.name))?;
+
+    let fields = match serde_yml::from_str(&rendered)
+        .with_context(|| format!("Failed to parse role `{}`", role.name))?
+    {
+        JsonValue::Object(fields) => fields,
+        _ => return Err(anyhow!("Role `{}` must be a map", role.name)),
+    };
+
+    // A role's includes, snippets and roles are its own
+    let mut actions = include::included(&fields, &directory, &contexts)?;
+
+    if let Some(JsonValue::Array(own)) = fields.get("actions") {
+        actions.extend(own.iter().cloned());
+    }
+
+    let mut actions = include::expand_actions(actions, &directory, &contexts, depth + 1)?;
+
+    let marker = serde_json::to_value(ActionRole {
+        root_dir: directory,
+        inputs,
+    })?;
+
+    for action in actions.iter_mut() {
+        if let Some(condition) = &role.condition {
+            include::add_condition(action, condition);
+        }
+
+        // Actions of roles used by this role keep their own
+        if let Some(fields) = action.as_object_mut() {
+            fields.entry(ROLE_FIELD).or_insert_with(|| marker.clone());
+        }
+    }
+
+    Ok(actions)
+}
+
+/// `roles/<name>` below the manifest root, or `<name>` in `source`. A
+/// `source` that's a role itself is used as is.
+fn role_directory(root: &Path, name: &str, source: Option<&str>) -> Result<PathBuf> {
+    let roles = match source {
+        None => root.join("roles"),
+        Some(source) => {
+            let local = root.join(source);
+
+            let roles = match local.is_dir() {
+                true => local,
+                false => fetch_roles(source)
+                    .ok_or_else(|| anyhow!("Failed to fetch the roles in `{}`", source))?,
+            };
+
+            if roles.join("role.yaml").is_file() {
+                return Ok(roles);
+            }
+
+            roles
+        }
+    };
+
+    let directory = roles.join(name);
+
+    match directory.join("role.yaml").is_file()
+        && directory.canonicalize().is_ok_and(|directory| {
+            roles
+                .canonicalize()
+                .is_ok_and(|roles| directory.starts_with(roles))
+        }) {
+        true => Ok(directory),
+        false => Err(anyhow!(
+            "Failed to find role `{}`, there's no {}",
+            name,
+            directory.join("role.yaml").display()
+        )),
+    }
+}
+
+/// The git repositories `action: role`s in `contents` take their roles
+/// from. Manifests aren't rendered yet when they're looked for, so they're
+/// found in the text of each list item, table or object with a role action,
+/// up to the next one that isn't nested in it.
+pub(crate) fn remote_role_sources(contents: &str) -> Vec<String> {
+    static PATTERNS: OnceLock<(Regex, Regex, Regex)> = OnceLock::new();
+
+    let (item, action, source) = PATTERNS.get_or_init(|| {
+        (
+            Regex::new(r"(?m)^([ \t]*)(?:-\s|\[\[|\{)").expect("Failed to compile the item regex"),
+            Regex::new(r#"\baction"?\s*[:=]\s*["']?role\b"#)
+                .expect("Failed to compile the action regex"),
+            Regex::new(r#"\bsource"?\s*[:=]\s*["']?((?:https|git|ssh)://[^\s"',}\]]+)"#)
+                .expect("Failed to compile the source regex"),
+        )
+    });
+
+    let items: Vec<(usize, usize)> = item
+        .captures_iter(contents)
+        .map(|captures| (captures.get(0).map_or(0, |m| m.start()), captures[1].len()))
+        .collect();
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, (start, indent))| {
+            let end = items[i + 1..]
+                .iter()
+                .find(|(_, other)| other <= indent)
+                .map_or(contents.len(), |(end, _)| *end);
+
+            &contents[*start..end]
+        })
+        .filter(|chunk| action.is_match(chunk))
+        .filter_map(|chunk| source.captures(chunk))
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// The directories of the remote roles in `sources`, fetching them when
+/// they haven't been yet
+pub(crate) fn remote_role_directories(sources: &[String]) -> Vec<PathBuf> {
+    let mut uris: Vec<String> = sources
+        .iter()
+        .flat_map(|contents| remote_role_sources(contents))
+        .collect();
+    uris.sort();
+    uris.dedup();
+
+    uris.iter().filter_map(|uri| fetch_roles(uri)).collect()
+}
+
+/// The roles in a repository, cloning it when it hasn't been. A source
+/// ending in `#ref:path` has them in the directory `path` of the repository.
+fn fetch_roles(source: &str) -> Option<PathBuf> {
+    let (repository, path) = split_source(source);
+    let repository = locate(&repository)?;
+
+    Some(match path {
+        Some(path) => repository.join(path),
+        None => repository,
+    })
+}
+
+/// A source without the `:path` at its end, and that path
+fn split_source(source: &str) -> (String, Option<&str>) {
+    let Some((repository, (reference, path))) = source
+        .split_once('#')
+        .and_then(|(repository, fragment)| Some((repository, fragment.split_once(':')?)))
+    else {
+        return (source.to_string(), None);
+    };
+
+    let repository = match reference.is_empty() {
+        true => repository.to_string(),
+        false => format!("{}#{}", repository, reference),
+    };
+
+    (repository, Some(path).filter(|path| !path.is_empty()))
+}
+
+/// The given inputs, checked against their declarations, and the defaults
+/// of those that aren't given
+fn resolve_inputs(
+    role: &str,
+    declared: &BTreeMap<String, RoleInput>,
+    given: &JsonMap<String, JsonValue>,
+) -> Result<JsonMap<String, JsonValue>> {
+    if let Some(name) = given.keys().find(|name| !declared.contains_key(*name)) {
+        return Err(anyhow!("Role `{}` has no input `{}`", role, name));
+    }
+
+    declared
+        .iter()
+        .map(|(name, input)| {
+            let value = given
+                .get(name)
+                .or(input.default.as_ref())
+                .ok_or_else(|| anyhow!("Role `{}` requires the input `{}`", role, name))?;
+
+            if !input.kind.accepts(value) {
+                return Err(anyhow!(
+                    "Input `{}` of role `{}` must be a {}, not {}",
+                    name,
+                    role,
+                    input.kind,
+                    value
+                ));
+            }
+
+            Ok((name.clone(), value.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn nvim_role(root: &Path) -> Result<()> {
+        let role = root.join("roles").join("nvim");
+        std::fs::create_dir_all(role.join("files"))?;
+        std::fs::write(role.join("files").join("init.lua"), "")?;
+
+        std::fs::write(
+            role.join("role.yaml"),
+            r#"
+description: Neovim and its config
+inputs:
+  version:
+    type: string
+    default: stable
+    description: The release to install
+  config_dir:
+    type: string
+    default: "{{ user.home_dir }}/.config/nvim"
+  plugins:
+    type: list
+
+actions:
+  - action: command.run
+    command: install-nvim
+    args: ["{{ inputs.version }}"]
+  - action: file.copy
+    from: init.lua
+    to: "{{ inputs.config_dir }}/init.lua"
+    where: inputs.plugins.len() > 0
+"#,
+        )?;
+
+        Ok(())
+    }
+
+    fn contexts() -> Contexts {
+        Contexts::from([(
+            String::from("user"),
+            BTreeMap::from([(String::from("home_dir"), Value::from("/home/jack"))]),
+        )])
+    }
+
+    #[test]
+    fn it_can_expand_roles() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        nvim_role(&root)?;
+
+        let action = json!({
+            "action": "role",
+            "name": "nvim",
+            "inputs": { "plugins": ["telescope"] },
+            "where": "os.name == \"linux\""
+        });
+
+        let actions = expand_role(&action, &root, &contexts(), 0)?;
+        let role = json!({
+            "root_dir": root.join("roles").join("nvim"),
+            "inputs": {
+                "config_dir": "/home/jack/.config/nvim",
+                "plugins": ["telescope"],
+                "version": "stable"
+            }
+        });
+
+        assert_eq!(
+            vec![
+                json!({
+                    "action": "command.run",
+                    "command": "install-nvim",
+                    "args": ["stable"],
+                    "where": "os.name == \"linux\"",
+                    "$role": role
+                }),
+                json!({
+                    "action": "file.copy",
+                    "from": "init.lua",
+                    "to": "/home/jack/.config/nvim/init.lua",
+                    "where": "(os.name == \"linux\") && (inputs.plugins.len() > 0)",
+                    "$role": role
+                }),
+            ],
+            actions
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_checks_inputs() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        nvim_role(&root)?;
+
+        let expand = |inputs: JsonValue| {
+            expand_role(
+                &json!({ "action": "role", "name": "nvim", "inputs": inputs }),
+                &root,
+                &contexts(),
+                0,
+            )
+            .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(
+            Err(String::from("Role `nvim` requires the input `plugins`")),
+            expand(json!({}))
+        );
+        assert_eq!(
+            Err(String::from("Role `nvim` has no input `plugin`")),
+            expand(json!({ "plugin": [] }))
+        );
+        assert_eq!(
+            Err(String::from(
+                "Input `version` of role `nvim` must be a string, not 10"
+            )),
+            expand(json!({ "plugins": [], "version": 10 }))
+        );
+        assert!(expand(json!({ "plugins": [] })).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_splits_the_path_off_sources() {
+        assert_eq!(
+            (
+                String::from("https://github.com/comtrya/roles#main"),
+                Some("roles")
+            ),
+            split_source("https://github.com/comtrya/roles#main:roles")
+        );
+        assert_eq!(
+            (
+                String::from("https://github.com/comtrya/roles"),
+                Some("roles")
+            ),
+            split_source("https://github.com/comtrya/roles#:roles")
+        );
+        assert_eq!(
+            (String::from("https://github.com/comtrya/roles#main"), None),
+            split_source("https://github.com/comtrya/roles#main:")
+        );
+        assert_eq!(
+            (String::from("https://github.com/comtrya/roles#main"), None),
+            split_source("https://github.com/comtrya/roles#main")
+        );
+        assert_eq!(
+            (String::from("ssh://git@example.com:22/roles"), None),
+            split_source("ssh://git@example.com:22/roles")
+        );
+    }
+
+    #[test]
+    fn it_finds_remote_role_sources() {
+        let yaml = r#"
+actions:
+  - action: file.download
+    source: https://example.com/archive.tar.gz
+    to: /tmp/archive.tar.gz
+
+  - action: role
+    name: nvim
+    inputs:
+      plugins:
+        - telescope
+    source: https://github.com/comtrya/roles#main
+
+  - action: role
+    name: local
+    source: ../roles
+"#;
+
+        assert_eq!(
+            vec![String::from("https://github.com/comtrya/roles#main")],
+            remote_role_sources(yaml)
+        );
+
+        let toml = r#"
+[[actions]]
+action = "role"
+name = "nvim"
+source = "git://example.com/roles"
+"#;
+
+        assert_eq!(
+            vec![String::from("git://example.com/roles")],
+            remote_role_sources(toml)
+        );
+    }
+}
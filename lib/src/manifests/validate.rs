@@ -0,0 +1,943 @@
+use super::{
+    format::Format, include, resolve_dependency, roles, with_deps, Manifest, ManifestSource,
+};
+use crate::actions::{Actions, ACTION_FIELDS, VARIANT_FIELDS};
+use crate::contexts::Contexts;
+use crate::rhai_functions;
+use crate::values::Value;
+use serde_json::Value as JsonValue;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// A problem found in a manifest, pointing at where it is in the file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    /// 1-based, 0 when the problem isn't on any particular line
+    pub line: usize,
+    /// 1-based
+    pub column: usize,
+    pub message: String,
+    /// The line the problem is on
+    pub snippet: Option<String>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+
+        let Some(snippet) = &self.snippet else {
+            return writeln!(f, "  --> {}", self.path.display());
+        };
+
+        let gutter = " ".repeat(self.line.to_string().len());
+        // Columns count characters, the snippet is sliced by bytes
+        let start = snippet
+            .char_indices()
+            .nth(self.column.saturating_sub(1))
+            .map_or(snippet.len(), |(index, _)| index);
+        let marker = snippet[start..]
+            .split(|c: char| c.is_whitespace() || c == ':' || c == '=')
+            .next()
+            .map_or(1, |word| word.chars().count().max(1));
+
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter,
+            self.path.display(),
+            self.line,
+            self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, snippet)?;
+        writeln!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(snippet[..start].chars().count()),
+            "^".repeat(marker)
+        )
+    }
+}
+
+/// Checks every manifest without running anything: that it renders and
+/// parses, that its actions and fields exist, that `chmod`s are octal, that
+/// the files it copies are there and that its `where` conditions compile.
+pub fn validate(sources: &[ManifestSource], contexts: &Contexts) -> Vec<Diagnostic> {
+    let names: Vec<&str> = sources.iter().map(|source| source.name.as_str()).collect();
+
+    // The outputs of dependencies are only known once they ran
+    let outputs: BTreeMap<String, BTreeMap<String, Value>> = sources
+        .iter()
+        .map(|source| (source.name.clone(), placeholder_outputs(source, contexts)))
+        .collect();
+
+    sources
+        .iter()
+        .flat_map(|source| {
+            let mut validator = Validator {
+                source,
+                rendered: source.contents.clone(),
+                diagnostics: vec![],
+            };
+
+            validator.validate(contexts, &names, &outputs);
+            validator.diagnostics
+        })
+        .collect()
+}
+
+/// The outputs of a manifest, with empty values, as far as they're known
+/// before it runs
+fn placeholder_outputs(source: &ManifestSource, contexts: &Contexts) -> BTreeMap<String, Value> {
+    let outputs = match source.outline(contexts) {
+        Ok(mut manifest) => manifest.remove("outputs"),
+        Err(_) => None,
+    };
+
+    match outputs {
+        Some(JsonValue::Object(outputs)) => outputs
+            .into_iter()
+            .map(|(name, _)| (name, Value::from("")))
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+struct Validator<'a> {
+    source: &'a ManifestSource,
+    /// Problems are found in the rendered manifest, but reported on the
+    /// lines of the source they came from when there's one
+    rendered: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn validate(
+        &mut self,
+        contexts: &Contexts,
+        names: &[&str],
+        outputs: &BTreeMap<String, BTreeMap<String, Value>>,
+    ) {
+        let deps = self.validate_dependencies(contexts, names, outputs);
+        let contexts = with_deps(contexts, &deps);
+
+        let contexts = match self.source.contexts(&contexts) {
+            Ok(contexts) => contexts,
+            // Lua manifests are run to find their labels
+            Err(err) if self.source.is_lua() => {
+                let (message, position) = lua_error(&err);
+                let (line, column) = position.unwrap_or((0, 1));
+
+                return self.report(line, column, message);
+            }
+            // The header is read from the whole manifest, which may not parse
+            Err(err) => match self.source.outline_str(&contexts) {
+                Ok(rendered) => {
+                    self.rendered = rendered;
+
+                    return match self.parse_rendered() {
+                        Err((message, Some((line, column)))) => self.report(line, column, message),
+                        _ => self.report_template_error(&err),
+                    };
+                }
+                Err(_) => return self.report_template_error(&err),
+            },
+        };
+
+        let manifest = match self.source.is_lua() {
+            true => self
+                .source
+                .evaluate(&contexts)
+                .map_err(|err| lua_error(&err)),
+            false => match self.source.render_str(&contexts) {
+                Ok(rendered) => {
+                    self.rendered = rendered;
+                    self.parse_rendered()
+                }
+                Err(err) => return self.report_template_error(&err),
+            },
+        };
+
+        let manifest = match manifest {
+            Ok(JsonValue::Object(manifest)) => manifest,
+            Ok(JsonValue::Null) => return,
+            Ok(_) => return self.report(0, 1, String::from("a manifest must be a map")),
+            Err((message, Some((line, column)))) => return self.report(line, column, message),
+            Err((message, None)) => return self.report(0, 1, message),
+        };
+
+        self.validate_manifest(&manifest);
+
+        if let Err(err) = include::included(&manifest, &self.source.root, &contexts) {
+            self.report_include_error(None, &err);
+        }
+
+        let actions = match manifest.get("actions") {
+            Some(JsonValue::Array(actions)) => actions.as_slice(),
+            _ => &[],
+        };
+
+        let lines = self.action_lines();
+
+        for (index, action) in actions.iter().enumerate() {
+            let lines = lines.get(index).cloned().unwrap_or(0..0);
+
+            match include::is_snippet(action) || roles::is_role(action) {
+                true => self.validate_expansion(action, lines, &contexts),
+                false => self.validate_action(action, lines),
+            }
+        }
+    }
+
+    /// The rendered manifest, or the error and where it is
+    fn parse_rendered(&self) -> Result<JsonValue, (String, Option<(usize, usize)>)> {
+        match self.source.format() {
+            Format::Toml => toml::from_str::<JsonValue>(&self.rendered).map_err(|err| {
+                let position = err
+                    .span()
+                    .map(|span| position_of(&self.rendered, span.start));
+
+                let message = err.message().lines().collect::<Vec<&str>>().join(", ");
+
+                (message, position)
+            }),
+            Format::Json => serde_json::from_str::<JsonValue>(&self.rendered).map_err(|err| {
+                let position = (err.line() > 0).then(|| (err.line() - 1, err.column()));
+                let message = err.to_string();
+                let message = match message.find(" at line ") {
+                    Some(end) => message[..end].to_string(),
+                    None => message,
+                };
+
+                (message, position)
+            }),
+            Format::Json5 => json5::from_str::<JsonValue>(&self.rendered).map_err(|err| {
+                let json5::Error::Message { msg, location } = err;
+                let position = location.map(|location| (location.line - 1, location.column));
+                let message = msg.lines().next().unwrap_or_default().to_string();
+
+                (message, position)
+            }),
+            _ => serde_yml::from_str::<JsonValue>(&self.rendered).map_err(|err| {
+                let position = err
+                    .location()
+                    .map(|location| (location.line() - 1, location.column()));
+                let message = err.to_string();
+                let message = match message.find(" at line ") {
+                    Some(end) => message[..end].to_string(),
+                    None => message,
+                };
+
+                (message, position)
+            }),
+        }
+    }
+
+    /// The snippet or role has to exist and render, and its actions have to
+    /// parse
+    fn validate_expansion(&mut self, action: &JsonValue, lines: Range<usize>, contexts: &Contexts) {
+        let lines = Some(lines);
+
+        if let Some(JsonValue::String(condition)) = action.get("where") {
+            self.validate_condition(lines.clone(), condition);
+        }
+
+        let actions =
+            match include::expand_actions(vec![action.clone()], &self.source.root, contexts, 0) {
+                Ok(actions) => actions,
+                Err(err) => return self.report_include_error(lines, &err),
+            };
+
+        let (key, kind) = match include::is_snippet(action) {
+            true => ("snippet", "snippet"),
+            false => ("name", "role"),
+        };
+        let name = action.get(key).and_then(JsonValue::as_str);
+
+        for action in actions {
+            if let Err(err) = serde_json::from_value::<Actions>(action) {
+                self.report_at_key(
+                    lines.clone(),
+                    key,
+                    format!(
+                        "invalid action in {} `{}`: {}",
+                        kind,
+                        name.unwrap_or_default(),
+                        err
+                    ),
+                );
+            }
+        }
+    }
+
+    fn report_include_error(&mut self, lines: Option<Range<usize>>, err: &anyhow::Error) {
+        let message = format!("{:#}", err);
+
+        match backticked(&message).first().map(|text| text.to_string()) {
+            Some(text) => self.report_at_text(lines, &text, message),
+            None => self.report_lines(lines, message),
+        }
+    }
+
+    /// Reports dependencies that don't exist, returning placeholders for the
+    /// outputs of those that do
+    fn validate_dependencies(
+        &mut self,
+        contexts: &Contexts,
+        names: &[&str],
+        outputs: &BTreeMap<String, BTreeMap<String, Value>>,
+    ) -> BTreeMap<String, BTreeMap<String, Value>> {
+        let mut deps = BTreeMap::new();
+
+        // A header that doesn't render is reported when rendering the manifest
+        let Ok(header) = self.source.header(contexts) else {
+            return deps;
+        };
+
+        for (index, dependency) in header.depends.iter().chain(&header.after).enumerate() {
+            match resolve_dependency(&self.source.name, dependency, names) {
+                Ok(resolved) if index < header.depends.len() => {
+                    deps.extend(resolved.into_iter().filter_map(|name| {
+                        outputs
+                            .get(&name)
+                            .map(|outputs| (name.clone(), outputs.clone()))
+                    }));
+                }
+                Ok(_) => {}
+                Err(err) => self.report_at_text(None, dependency, err.to_string()),
+            }
+        }
+
+        deps
+    }
+
+    fn validate_manifest(&mut self, manifest: &serde_json::Map<String, JsonValue>) {
+        let fields = crate::utilities::struct_fields::<Manifest>().unwrap_or_default();
+
+        for key in manifest.keys() {
+            if !fields.contains(&key.as_str()) {
+                self.report_unknown_field(None, key, "the manifest", fields);
+            }
+        }
+
+        // Actions are checked one by one, with better errors
+        let mut header = manifest.clone();
+        header.remove("actions");
+
+        if let Err(err) = serde_path_to_error::deserialize::<_, Manifest>(JsonValue::Object(header))
+        {
+            let key = err.path().iter().next().map(|key| key.to_string());
+            let message = format!("invalid `{}`: {}", err.path(), err.inner());
+
+            match key {
+                Some(key) => self.report_at_key(None, &key, message),
+                None => self.report(0, 1, message),
+            }
+        }
+
+        if let Some(JsonValue::String(condition)) = manifest.get("where") {
+            self.validate_condition(None, condition);
+        }
+    }
+
+    fn validate_action(&mut self, action: &JsonValue, lines: Range<usize>) {
+        let lines = Some(lines);
+        let reported = self.diagnostics.len();
+
+        let Some(fields) = action.as_object() else {
+            return self.report_lines(lines, String::from("an action must be a map"));
+        };
+
+        let Some(name) = fields.get("action").and_then(JsonValue::as_str) else {
+            return self.report_lines(lines, String::from("missing `action`"));
+        };
+
+        let parsed = serde_json::from_value::<Actions>(action.clone());
+
+        if let Err(err) = &parsed {
+            let message = err.to_string();
+
+            if message.starts_with("unknown variant") {
+                let known: Vec<&str> = backticked(&message).into_iter().skip(1).collect();
+                let message = match suggest(name, known) {
+                    Some(suggestion) => {
+                        format!("unknown action `{}`, did you mean `{}`?", name, suggestion)
+                    }
+                    None => format!("unknown action `{}`", name),
+                };
+
+                return self.report_at_key(lines, "action", message);
+            }
+        }
+
+        let variants: Vec<&serde_json::Map<String, JsonValue>> = match fields.get("variants") {
+            Some(JsonValue::Array(variants)) => {
+                variants.iter().filter_map(JsonValue::as_object).collect()
+            }
+            _ => vec![],
+        };
+
+        if let Some(action_fields) = Actions::fields(name) {
+            let known: Vec<&str> = ACTION_FIELDS.iter().chain(action_fields).copied().collect();
+
+            for key in fields.keys() {
+                if !known.contains(&key.as_str()) {
+                    self.report_unknown_field(lines.clone(), key, name, &known);
+                }
+            }
+
+            let known: Vec<&str> = VARIANT_FIELDS
+                .iter()
+                .chain(action_fields)
+                .copied()
+                .collect();
+
+            for key in variants.iter().flat_map(|variant| variant.keys()) {
+                if !known.contains(&key.as_str()) {
+                    self.report_unknown_field(lines.clone(), key, "a variant", &known);
+                }
+            }
+        }
+
+        for fields in std::iter::once(fields).chain(variants.iter().copied()) {
+            if let Some(chmod) = fields.get("chmod") {
+                self.validate_chmod(lines.clone(), chmod);
+            }
+
+            if let Some(JsonValue::String(condition)) = fields.get("where") {
+                self.validate_condition(lines.clone(), condition);
+            }
+        }
+
+        let parsed = match parsed {
+            Ok(parsed) => parsed,
+            // A misspelled field or a chmod that isn't octal fail to
+            // deserialize too, the problems reported above say why
+            Err(_) if self.diagnostics.len() > reported => return,
+            Err(err) => {
+                return self.report_lines(lines, format!("invalid `{}` action: {}", name, err))
+            }
+        };
+
+        let manifest = Manifest {
+            root_dir: self.source.path.parent().map(Path::to_path_buf),
+            ..Default::default()
+        };
+
+        if let Some(files) = manifest.files_dir() {
+            for file in parsed.files() {
+                let message = match file.resolve_in(&manifest, &files) {
+                    Ok(path) if path.exists() => continue,
+                    Ok(path) if path.starts_with(&files) => {
+                        format!("`{}` doesn't exist in {}", file, files.display())
+                    }
+                    Ok(path) => format!("`{}` doesn't exist, it's {}", file, path.display()),
+                    Err(err) => err.to_string(),
+                };
+
+                self.report_at_text(lines.clone(), file.as_str(), message);
+            }
+        }
+    }
+
+    fn validate_chmod(&mut self, lines: Option<Range<usize>>, chmod: &JsonValue) {
+        let message = match chmod {
+            JsonValue::String(chmod) if u32::from_str_radix(chmod, 8).is_ok() => return,
+            JsonValue::String(chmod) => format!("`{}` isn't a valid octal chmod", chmod),
+            chmod => format!(
+                "chmod must be a quoted octal string, e.g. \"0644\", not `{}`",
+                chmod
+            ),
+        };
+
+        self.report_at_key(lines, "chmod", message);
+    }
+
+    fn validate_condition(&mut self, lines: Option<Range<usize>>, condition: &str) {
+        if let Err(err) = rhai_functions::engine().compile_expression(condition) {
+            self.report_at_text(
+                lines,
+                condition,
+                format!("invalid `where` condition `{}`: {}", condition, err),
+            );
+        }
+    }
+
+    fn report_unknown_field(
+        &mut self,
+        lines: Option<Range<usize>>,
+        key: &str,
+        of: &str,
+        known: &[&str],
+    ) {
+        let message = match suggest(key, known.iter().copied()) {
+            Some(suggestion) => format!(
+                "unknown field `{}` in {}, did you mean `{}`?",
+                key, of, suggestion
+            ),
+            None => format!("unknown field `{}` in {}", key, of),
+        };
+
+        self.report_at_key(lines, key, message);
+    }
+
+    /// Tera errors only know about the template they failed in, the line
+    /// they give is found in the source by its contents
+    fn report_template_error(&mut self, err: &anyhow::Error) {
+        let messages: Vec<String> = err.chain().map(|err| err.to_string()).collect();
+
+        // Syntax errors come with the line, and what was expected
+        if let Some(parse_error) = messages.iter().find(|message| message.contains("--> ")) {
+            let position = parse_error
+                .split_once("--> ")
+                .and_then(|(_, rest)| rest.lines().next())
+                .and_then(|position| position.trim().split_once(':'))
+                .and_then(|(line, column)| {
+                    Some((line.parse::<usize>().ok()?, column.parse::<usize>().ok()?))
+                });
+            let text = parse_error.lines().find_map(|line| {
+                let (number, text) = line.split_once(" | ")?;
+                number.trim().parse::<usize>().ok().map(|_| text)
+            });
+            let expected = parse_error
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("= "))
+                .unwrap_or("invalid syntax");
+
+            let message = format!("invalid template: {}", expected);
+
+            match (text, position) {
+                (Some(text), Some((_, column))) => match self.find_text(0..usize::MAX, text) {
+                    Some((line, _)) => self.report(line, column, message),
+                    None => self.report(0, 1, message),
+                },
+                (_, Some((line, column))) => self.report(line.saturating_sub(1), column, message),
+                _ => self.report(0, 1, message),
+            }
+
+            return;
+        }
+
+        let message = messages.last().cloned().unwrap_or_default();
+        let message = match message.find(" while rendering") {
+            Some(end) => message[..end].to_string(),
+            None => message,
+        };
+
+        match backticked(&message).first().map(|name| name.to_string()) {
+            Some(name) => self.report_at_text(None, &name, message),
+            None => self.report(0, 1, message),
+        }
+    }
+
+    /// `line` is 0-based, into the rendered manifest
+    fn report(&mut self, line: usize, column: usize, message: String) {
+        let rendered = self.rendered.lines().nth(line);
+        let source: Vec<&str> = self.source.contents.lines().collect();
+
+        let (line, snippet) = match rendered {
+            Some(rendered) if source.get(line) == Some(&rendered) => (line + 1, Some(rendered)),
+            Some(rendered) => match source.iter().position(|source| *source == rendered) {
+                Some(line) => (line + 1, Some(rendered)),
+                None => (line + 1, Some(rendered)),
+            },
+            None => (0, None),
+        };
+
+        self.diagnostics.push(Diagnostic {
+            path: self.source.path.clone(),
+            line,
+            column,
+            message,
+            snippet: snippet.map(str::to_string),
+        });
+    }
+
+    fn report_lines(&mut self, lines: Option<Range<usize>>, message: String) {
+        let line = lines.map_or(0, |lines| lines.start);
+        let column = self.indentation(line);
+
+        self.report(line, column, message)
+    }
+
+    fn report_at_key(&mut self, lines: Option<Range<usize>>, key: &str, message: String) {
+        match self.find_key(lines.clone(), key) {
+            Some((line, column)) => self.report(line, column, message),
+            None => self.report_lines(lines, message),
+        }
+    }
+
+    fn report_at_text(&mut self, lines: Option<Range<usize>>, text: &str, message: String) {
+        let found = lines
+            .clone()
+            .and_then(|lines| self.find_text(lines, text))
+            .or_else(|| self.find_text(0..usize::MAX, text));
+
+        match found {
+            Some((line, column)) => self.report(line, column, message),
+            None => self.report_lines(lines, message),
+        }
+    }
+
+    fn indentation(&self, line: usize) -> usize {
+        self.rendered.lines().nth(line).map_or(1, |text| {
+            let trimmed = text.trim_start();
+            let trimmed = trimmed.strip_prefix("- ").unwrap_or(trimmed).trim_start();
+
+            text.len() - trimmed.len() + 1
+        })
+    }
+
+    /// Where a `key:` or `key =` is in `lines`, or anywhere in the manifest
+    fn find_key(&self, lines: Option<Range<usize>>, key: &str) -> Option<(usize, usize)> {
+        let is_key = |text: &str| {
+            let trimmed = text.trim_start();
+            let trimmed = trimmed.strip_prefix("- ").unwrap_or(trimmed).trim_start();
+
+            [
+                key.to_string(),
+                format!("\"{}\"", key),
+                format!("'{}'", key),
+            ]
+            .iter()
+            .find_map(|quoted| trimmed.strip_prefix(quoted.as_str()))
+            .map(|rest| rest.trim_start())
+            .filter(|rest| rest.starts_with(':') || rest.starts_with('='))
+            .map(|_| text.len() - trimmed.len() + 1)
+        };
+
+        let find = |lines: Range<usize>| {
+            self.rendered
+                .lines()
+                .enumerate()
+                .skip(lines.start)
+                .take(lines.len())
+                .find_map(|(line, text)| is_key(text).map(|column| (line, column)))
+        };
+
+        lines.and_then(&find).or_else(|| find(0..usize::MAX))
+    }
+
+    fn find_text(&self, lines: Range<usize>, text: &str) -> Option<(usize, usize)> {
+        self.rendered
+            .lines()
+            .enumerate()
+            .skip(lines.start)
+            .take(lines.len())
+            .find_map(|(line, candidate)| {
+                candidate
+                    .find(text)
+                    .map(|index| (line, candidate[..index].chars().count() + 1))
+            })
+    }
+
+    /// The lines of every action, in order
+    fn action_lines(&self) -> Vec<Range<usize>> {
+        let starts: Vec<usize> = self
+            .rendered
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| match self.source.format() {
+                Format::Toml => text.trim() == "[[actions]]",
+                _ => {
+                    let trimmed = text.trim_start();
+                    let trimmed = trimmed.strip_prefix("- ").unwrap_or(trimmed);
+                    let trimmed = trimmed.strip_prefix('{').unwrap_or(trimmed).trim_start();
+
+                    ["action:", "\"action\":", "'action':"]
+                        .iter()
+                        .any(|key| trimmed.starts_with(key))
+                }
+            })
+            .map(|(line, _)| line)
+            .collect();
+
+        let end = self.rendered.lines().count();
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(index, start)| *start..starts.get(index + 1).copied().unwrap_or(end))
+            .collect()
+    }
+}
+
+/// The 0-based line and 1-based column, in characters, of a byte offset.
+/// Errors at the very end are on the last line.
+fn position_of(text: &str, offset: usize) -> (usize, usize) {
+    let offset = (0..=offset.min(text.len()))
+        .rev()
+        .find(|offset| text.is_char_boundary(*offset))
+        .unwrap_or_default();
+    let before = text[..offset].trim_end_matches('\n');
+    let line = before.matches('\n').count();
+    let column = before[before.rfind('\n').map_or(0, |newline| newline + 1)..]
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+/// The message of a Lua error, and the line it's on. Errors look like
+/// `runtime error: <file>:<line>: <message>`, followed by a traceback.
+fn lua_error(err: &anyhow::Error) -> (String, Option<(usize, usize)>) {
+    let message = err.to_string();
+    let first = message.lines().next().unwrap_or_default();
+
+    first
+        .match_indices(':')
+        .find_map(|(index, _)| {
+            let (line, message) = first[index + 1..].split_once(": ")?;
+            let line = line.parse::<usize>().ok()?;
+
+            Some((message.to_string(), Some((line.saturating_sub(1), 1))))
+        })
+        .unwrap_or_else(|| (first.to_string(), None))
+}
+
+/// The names quoted in backticks in an error message
+fn backticked(message: &str) -> Vec<&str> {
+    message.split('`').skip(1).step_by(2).collect()
+}
+
+/// The closest of `candidates` to `name`, if any is close enough
+fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::jaro_winkler(name, candidate), candidate))
+        .filter(|(similarity, _)| *similarity > 0.8)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::DiscoveryConfig;
+    use crate::manifests::read;
+    use pretty_assertions::assert_eq;
+
+    fn validate_manifest(file_name: &str, contents: &str) -> Vec<(usize, usize, String)> {
+        let manifest_path = tempfile::tempdir().unwrap();
+        std::fs::create_dir(manifest_path.path().join("files")).unwrap();
+        std::fs::write(manifest_path.path().join("files").join("gitconfig"), "").unwrap();
+        std::fs::write(manifest_path.path().join(file_name), contents).unwrap();
+
+        validate(
+            &read(manifest_path.path(), &DiscoveryConfig::default()).unwrap(),
+            &Contexts::new(),
+        )
+        .into_iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.message))
+        .collect()
+    }
+
+    #[test]
+    fn it_reports_problems_with_actions() {
+        let diagnostics = validate_manifest(
+            "git.yaml",
+            r#"
+where: os.name ==
+actions:
+  - action: file.copy
+    from: gitconfig
+    to: ~/.gitconfig
+    chmod: "0999"
+
+  - action: file.copy
+    from: gitignore
+    to: ~/.gitignore
+
+  - action: comand.run
+    command: echo
+
+  - action: command.run
+    comand: echo
+    variants:
+      - where: os.name == "linux" &&
+        command: printf
+"#,
+        );
+
+        let positions: Vec<(usize, usize)> = diagnostics
+            .iter()
+            .map(|(line, column, _)| (*line, *column))
+            .collect();
+
+        assert_eq!(
+            vec![(2, 8), (7, 5), (10, 11), (13, 5), (17, 5), (19, 16)],
+            positions
+        );
+
+        assert!(diagnostics[0]
+            .2
+            .starts_with("invalid `where` condition `os.name ==`"));
+        assert_eq!("`0999` isn't a valid octal chmod", diagnostics[1].2);
+        assert!(diagnostics[2].2.starts_with("`gitignore` doesn't exist in"));
+        assert_eq!(
+            "unknown action `comand.run`, did you mean `command.run`?",
+            diagnostics[3].2
+        );
+        assert_eq!(
+            "unknown field `comand` in command.run, did you mean `command`?",
+            diagnostics[4].2
+        );
+        assert!(diagnostics[5].2.starts_with("invalid `where` condition"));
+    }
+
+    #[test]
+    fn it_reports_syntax_and_template_errors() {
+        assert_eq!(
+            vec![(4, 7, String::from("did not find expected ',' or ']'"))],
+            validate_manifest(
+                "broken.yaml",
+                "actions:\n  - action: command.run\n    command: [echo\n  oops: true\n"
+            )
+        );
+
+        let diagnostics = validate_manifest(
+            "toml.toml",
+            "[[actions]]\naction = \"command.run\"\ncommand = \"echo\"\nargs = [\"a\"\n",
+        );
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(4, diagnostics[0].0);
+
+        assert_eq!(
+            vec![(
+                3,
+                18,
+                String::from("Variable `variables.missing` not found in context")
+            )],
+            validate_manifest(
+                "variable.yaml",
+                "actions:\n  - action: command.run\n    command: \"{{ variables.missing }}\"\n",
+            )
+        );
+
+        let diagnostics = validate_manifest(
+            "syntax.yaml",
+            "actions:\n  - action: command.run\n    command: \"{{ variables.ship | }}\"\n",
+        );
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(3, diagnostics[0].0);
+        assert!(diagnostics[0].2.starts_with("invalid template"));
+    }
+
+    #[test]
+    fn it_accepts_valid_manifests() {
+        assert_eq!(
+            Vec::<(usize, usize, String)>::new(),
+            validate_manifest(
+                "valid.yaml",
+                r#"
+where: os.name == "linux"
+actions:
+  - action: file.copy
+    source: gitconfig
+    target: ~/.gitconfig
+    chmod: "0644"
+  - action: cmd.run
+    command: echo
+    sudo: false
+    variants:
+      - where: os.name == "macos"
+        command: printf
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn it_reports_problems_with_snippets() {
+        let diagnostics = validate_manifest(
+            "k9s.yaml",
+            r#"
+include:
+  - common/missing.yaml
+actions:
+  - action: include
+    snippet: github-cli
+    with:
+      name: k9s
+"#,
+        );
+
+        assert_eq!(2, diagnostics.len());
+        assert_eq!((3, 5), (diagnostics[0].0, diagnostics[0].1));
+        assert!(diagnostics[0].2.contains("`common/missing.yaml`"));
+        assert_eq!((6, 14), (diagnostics[1].0, diagnostics[1].1));
+        assert!(diagnostics[1].2.contains("`github-cli`"));
+    }
+
+    #[test]
+    fn it_reports_lua_errors() {
+        let diagnostics = validate_manifest(
+            "sgc.lua",
+            "local base = nil\n\nreturn {\n  actions = { base.actions },\n}\n",
+        );
+
+        assert_eq!(1, diagnostics.len());
+        assert_eq!((4, 1), (diagnostics[0].0, diagnostics[0].1));
+        assert!(diagnostics[0].2.contains("attempt to index a nil value"));
+    }
+
+    #[test]
+    fn it_reports_missing_roles() {
+        let diagnostics = validate_manifest(
+            "editor.yaml",
+            r#"
+actions:
+  - action: role
+    name: nvim
+    inputs:
+      leader: space
+"#,
+        );
+
+        assert_eq!(1, diagnostics.len());
+        assert_eq!((4, 11), (diagnostics[0].0, diagnostics[0].1));
+        assert!(diagnostics[0].2.contains("Failed to find role `nvim`"));
+    }
+
+    #[test]
+    fn it_can_print_diagnostics() {
+        let diagnostic = Diagnostic {
+            path: PathBuf::from("git.yaml"),
+            line: 17,
+            column: 5,
+            message: String::from("unknown field `comand` in command.run"),
+            snippet: Some(String::from("    comand: echo")),
+        };
+
+        assert_eq!(
+            "error: unknown field `comand` in command.run\n  --> git.yaml:17:5\n   |\n17 |     comand: echo\n   |     ^^^^^^\n",
+            diagnostic.to_string()
+        );
+
+        let diagnostic = Diagnostic {
+            path: PathBuf::from("de.yaml"),
+            line: 3,
+            column: 6,
+            message: String::from("unknown field `rger`"),
+            snippet: Some(String::from("  - ärger: ö")),
+        };
+
+        assert_eq!(
+            "error: unknown field `rger`\n --> de.yaml:3:6\n  |\n3 |   - ärger: ö\n  |      ^^^^\n",
+            diagnostic.to_string()
+        );
+
+        let diagnostic = Diagnostic {
+            column: 40,
+            ..diagnostic
+        };
+        assert!(diagnostic.to_string().ends_with("  |             ^\n"));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::utilities::expand_home;
+use gethostname::gethostname;
+use globset::Glob;
+use regex::Regex;
+use rhai::{Dynamic, Engine, EvalAltResult, Position, Scope, AST};
+use std::{any::Any, cell::RefCell, collections::HashMap};
+
+thread_local! {
+    static ENGINE: Engine = engine();
+    static ASTS: RefCell<HashMap<String, AST>> = RefCell::new(HashMap::new());
+}
+
+/// An engine with comtrya's helper functions registered
+pub fn engine() -> Engine {
+    let mut engine = Engine::new();
+    register_functions(&mut engine);
+
+    engine
+}
+
+pub fn register_functions(engine: &mut Engine) {
+    engine.register_fn("command_exists", command_exists);
+    engine.register_fn("file_exists", file_exists);
+    engine.register_fn("env_or", env_or);
+    engine.register_fn("semver_gte", semver_gte);
+    engine.register_fn("hostname_matches", hostname_matches);
+    engine.register_fn("regex_match", regex_match);
+}
+
+/// Evaluates an expression, e.g. a `where` condition, with the shared engine.
+/// Compiled expressions are cached, so conditions repeated across manifests
+/// and actions are only parsed once.
+pub fn eval<T: Any + Clone>(scope: &mut Scope, expression: &str) -> Result<T, Box<EvalAltResult>> {
+    ENGINE.with(|engine| {
+        let ast = ASTS.with(|asts| -> Result<AST, Box<EvalAltResult>> {
+            if let Some(ast) = asts.borrow().get(expression) {
+                return Ok(ast.clone());
+            }
+
+            let ast = engine.compile(expression)?;
+            asts.borrow_mut()
+                .insert(expression.to_string(), ast.clone());
+
+            Ok(ast)
+        })?;
+
+        let result = engine.eval_ast_with_scope::<Dynamic>(scope, &ast)?;
+        let type_name = result.type_name();
+
+        result.try_cast::<T>().ok_or_else(|| {
+            EvalAltResult::ErrorMismatchOutputType(
+                engine.map_type_name(std::any::type_name::<T>()).into(),
+                type_name.into(),
+                Position::NONE,
+            )
+            .into()
+        })
+    })
+}
+
+fn command_exists(command: &str) -> bool {
+    which::which(command).is_ok()
+}
+
+fn file_exists(path: &str) -> bool {
+    expand_home(path).exists()
+}
+
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+/// Compares dotted versions numerically. Unlike strict semver, versions
+/// such as `22.04` or `14` are accepted and missing parts count as zero.
+fn semver_gte(version: &str, minimum: &str) -> bool {
+    let version = version_parts(version);
+    let minimum = version_parts(minimum);
+
+    (0..version.len().max(minimum.len()))
+        .map(|index| {
+            (
+                version.get(index).copied().unwrap_or(0),
+                minimum.get(index).copied().unwrap_or(0),
+            )
+        })
+        .find(|(version, minimum)| version != minimum)
+        .is_none_or(|(version, minimum)| version > minimum)
+}
+
+/// `v1.2.3-rc1` becomes `[1, 2, 3]`, anything after the numeric parts is ignored
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map_while(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+fn hostname_matches(pattern: &str) -> Result<bool, Box<EvalAltResult>> {
+    let glob = Glob::new(pattern).map_err(|error| error.to_string())?;
+
+    Ok(glob
+        .compile_matcher()
+        .is_match(gethostname().to_string_lossy().as_ref()))
+}
+
+fn regex_match(string: &str, pattern: &str) -> Result<bool, Box<EvalAltResult>> {
+    let regex = Regex::new(pattern).map_err(|error| error.to_string())?;
+
+    Ok(regex.is_match(string))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_can_compare_versions() {
+        assert!(semver_gte("22.04", "22.04"));
+        assert!(semver_gte("22.10", "22.04"));
+        assert!(semver_gte("24", "22.04"));
+        assert!(semver_gte("v1.2.3-rc1", "1.2"));
+        assert!(!semver_gte("20.04.6", "22.04"));
+        assert!(!semver_gte("1.2", "1.2.1"));
+    }
+
+    fn is_true(expression: &str) -> bool {
+        eval::<bool>(&mut Scope::new(), expression).unwrap()
+    }
+
+    #[test]
+    fn it_can_evaluate_helpers() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        std::env::set_var("COMTRYA_RHAI_TEST", "Jaffa");
+
+        assert!(is_true(r#"command_exists("sh")"#));
+        assert!(!is_true(r#"command_exists("comtrya-does-not-exist")"#));
+        assert!(is_true(&format!(
+            r#"file_exists("{}")"#,
+            file.path().display()
+        )));
+        assert!(is_true(
+            r#"env_or("COMTRYA_RHAI_TEST", "Tau'ri") == "Jaffa""#
+        ));
+        assert!(is_true(
+            r#"env_or("COMTRYA_RHAI_UNSET", "Tau'ri") == "Tau'ri""#
+        ));
+        assert!(is_true(r#"semver_gte("22.10", "22.04")"#));
+        assert!(is_true(r#"hostname_matches("*")"#));
+        assert!(is_true(r#"regex_match("work-laptop", "^work-")"#));
+        assert!(eval::<bool>(&mut Scope::new(), r#"regex_match("x", "(")"#).is_err());
+    }
+
+    #[test]
+    fn it_caches_compiled_expressions() {
+        let mut scope = Scope::new();
+        scope.push_constant("answer", 42_i64);
+
+        assert!(eval::<bool>(&mut scope, "answer == 42").unwrap());
+        assert!(eval::<bool>(&mut scope, "answer == 42").unwrap());
+        assert!(eval::<String>(&mut scope, "answer == 42").is_err());
+
+        ASTS.with(|asts| assert!(asts.borrow().contains_key("answer == 42")));
+    }
+}
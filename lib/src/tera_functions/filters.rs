@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use tera::{Result, Tera, Value};
+
+/// Tera's own `json_encode` covers JSON
+pub(super) fn register(tera: &mut Tera) {
+    tera.register_filter("shell_quote", shell_quote);
+    tera.register_filter("toml_encode", toml_encode);
+    tera.register_filter("yaml_encode", yaml_encode);
+}
+
+/// Quotes a string for POSIX shells. Lists are quoted item by item and
+/// joined with spaces, so they can be used as the arguments of a command.
+fn shell_quote(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let quote = |value: &Value| match value {
+        Value::String(string) => format!("'{}'", string.replace('\'', r"'\''")),
+        value => format!("'{}'", value),
+    };
+
+    Ok(Value::String(match value {
+        Value::Array(values) => values.iter().map(quote).collect::<Vec<_>>().join(" "),
+        value => quote(value),
+    }))
+}
+
+/// Maps are encoded as a TOML document, anything else as an inline value
+fn toml_encode(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let encoded = match value {
+        Value::Object(_) => toml::to_string(value).map_err(|error| error.to_string())?,
+        value => toml::Value::try_from(value)
+            .map_err(|error| error.to_string())?
+            .to_string(),
+    };
+
+    Ok(Value::String(encoded))
+}
+
+fn yaml_encode(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let encoded = serde_yml::to_string(value).map_err(|error| error.to_string())?;
+
+    Ok(Value::String(encoded.trim_end().to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tera_functions::render;
+    use pretty_assertions::assert_eq;
+    use tera::Context;
+
+    #[test]
+    fn it_can_use_the_filters() -> anyhow::Result<()> {
+        let mut context = Context::new();
+        context.insert("message", "it's done");
+        context.insert("args", &["ls", "-l", "my dir"]);
+        context.insert(
+            "settings",
+            &serde_json::json!({ "editor": "nvim", "tabs": [2, 4] }),
+        );
+
+        let render_str = |template: &str| render(None, template, &context);
+
+        assert_eq!(
+            r#"echo 'it'\''s done'"#,
+            render_str("echo {{ message | shell_quote }}")?
+        );
+        assert_eq!(
+            "'ls' '-l' 'my dir'",
+            render_str("{{ args | shell_quote }}")?
+        );
+
+        assert_eq!(
+            "editor = \"nvim\"\ntabs = [2, 4]\n",
+            render_str("{{ settings | toml_encode }}")?
+        );
+        assert_eq!(
+            "name = \"it's done\"",
+            render_str("name = {{ message | toml_encode }}")?
+        );
+
+        assert_eq!(
+            "editor: nvim\ntabs:\n- 2\n- 4",
+            render_str("{{ settings | yaml_encode }}")?
+        );
+        assert_eq!(
+            r#"{"editor":"nvim","tabs":[2,4]}"#,
+            render_str("{{ settings | json_encode }}")?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+use crate::utilities::expand_home;
+use anyhow::anyhow;
+use chrono::{Local, SecondsFormat, Utc};
+use gethostname::gethostname;
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::Path,
+    process::Command,
+};
+use tera::{from_value, Result, Tera, Value};
+
+/// The per-host secret `random_password` derives passwords from, in the
+/// local data directory of comtrya
+const SECRET_FILE: &str = "secret";
+const SECRET_LEN: usize = 32;
+
+pub(super) fn register(tera: &mut Tera) {
+    tera.register_function("env", env);
+    tera.register_function("command_output", command_output);
+    tera.register_function("which", which);
+    tera.register_function("path_exists", path_exists);
+    tera.register_function("glob", glob);
+    tera.register_function("sha256", sha256);
+    tera.register_function("hostname", hostname);
+    tera.register_function("expand_home", expand_home_dir);
+    tera.register_function("now", now);
+    tera.register_function("random_password", random_password);
+}
+
+/// The argument `name`, which has to be given
+fn required<T: serde::de::DeserializeOwned>(
+    function: &str,
+    args: &HashMap<String, Value>,
+    name: &str,
+) -> Result<T> {
+    match args.get(name) {
+        Some(value) => from_value(value.clone()).map_err(|_| {
+            format!(
+                "Function `{}` received {}={}, which isn't the expected type",
+                function, name, value
+            )
+            .into()
+        }),
+        None => Err(format!("Function `{}` requires the argument `{}`", function, name).into()),
+    }
+}
+
+/// The argument `name`, or `default` when it isn't given
+fn optional<T: serde::de::DeserializeOwned>(
+    function: &str,
+    args: &HashMap<String, Value>,
+    name: &str,
+    default: T,
+) -> Result<T> {
+    match args.contains_key(name) {
+        true => required(function, args, name),
+        false => Ok(default),
+    }
+}
+
+/// `env(name="EDITOR", default="vim")`, unset variables without a default
+/// are an error
+fn env(args: &HashMap<String, Value>) -> Result<Value> {
+    let name: String = required("env", args, "name")?;
+
+    match (std::env::var(&name), args.get("default")) {
+        (Ok(value), _) => Ok(Value::String(value)),
+        (Err(_), Some(default)) => Ok(default.clone()),
+        (Err(_), None) => Err(format!("Environment variable `{}` isn't set", name).into()),
+    }
+}
+
+/// The trimmed stdout of `command_output(cmd="git", args=["--version"])`.
+/// Commands that fail are an error.
+fn command_output(args: &HashMap<String, Value>) -> Result<Value> {
+    let cmd: String = required("command_output", args, "cmd")?;
+    let arguments: Vec<String> = optional("command_output", args, "args", vec![])?;
+
+    let output = Command::new(&cmd)
+        .args(&arguments)
+        .output()
+        .map_err(|error| format!("Failed to run `{}`: {}", cmd, error))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "`{}` failed with {}: {}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(Value::String(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+/// The path of a binary in `PATH`, or an empty string when there's none
+fn which(args: &HashMap<String, Value>) -> Result<Value> {
+    let name: String = required("which", args, "name")?;
+
+    Ok(Value::String(
+        which::which(name)
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    ))
+}
+
+fn path_exists(args: &HashMap<String, Value>) -> Result<Value> {
+    let path: String = required("path_exists", args, "path")?;
+
+    Ok(Value::Bool(expand_home(&path).exists()))
+}
+
+/// The paths matching `pattern`, sorted
+fn glob(args: &HashMap<String, Value>) -> Result<Value> {
+    let pattern: String = required("glob", args, "pattern")?;
+    let pattern = expand_home(&pattern);
+
+    let paths = glob::glob(&pattern.to_string_lossy())
+        .map_err(|error| format!("Invalid pattern `{}`: {}", pattern.display(), error))?
+        .filter_map(|path| path.ok())
+        .map(|path| Value::String(path.to_string_lossy().to_string()))
+        .collect();
+
+    Ok(Value::Array(paths))
+}
+
+/// The hex digest of `value`, or of the contents of the file at `path`
+fn sha256(args: &HashMap<String, Value>) -> Result<Value> {
+    let digest = match (args.get("value"), args.get("path")) {
+        (Some(_), None) => {
+            let value: String = required("sha256", args, "value")?;
+            sha256::digest(value)
+        }
+        (None, Some(_)) => {
+            let path: String = required("sha256", args, "path")?;
+            let path = expand_home(&path);
+
+            sha256::try_digest(path.as_path())
+                .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?
+        }
+        _ => return Err("Function `sha256` requires either `value` or `path`".into()),
+    };
+
+    Ok(Value::String(digest))
+}
+
+fn hostname(_: &HashMap<String, Value>) -> Result<Value> {
+    Ok(Value::String(gethostname().to_string_lossy().to_string()))
+}
+
+fn expand_home_dir(args: &HashMap<String, Value>) -> Result<Value> {
+    let path: String = required("expand_home", args, "path")?;
+
+    Ok(Value::String(
+        expand_home(&path).to_string_lossy().to_string(),
+    ))
+}
+
+/// The current time, formatted with `format` (strftime), as RFC 3339 by
+/// default. Like Tera's own `now`, `utc=true` uses UTC rather than local
+/// time and `timestamp=true` gives seconds since the epoch.
+fn now(args: &HashMap<String, Value>) -> Result<Value> {
+    let utc: bool = optional("now", args, "utc", false)?;
+    let format: Option<String> = optional("now", args, "format", None)?;
+
+    if optional("now", args, "timestamp", false)? {
+        return Ok(Value::from(Utc::now().timestamp()));
+    }
+
+    Ok(Value::String(match (utc, format) {
+        (true, Some(format)) => Utc::now().format(&format).to_string(),
+        (true, None) => Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        (false, Some(format)) => Local::now().format(&format).to_string(),
+        (false, None) => Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+    }))
+}
+
+/// `len` random letters and digits, the same on every run on this host for
+/// the same `seed`. They're derived from a secret generated once per host,
+/// so knowing the seed isn't enough to know the password.
+fn random_password(args: &HashMap<String, Value>) -> Result<Value> {
+    let len: usize = optional("random_password", args, "len", 32)?;
+    let seed: String = required("random_password", args, "seed")?;
+
+    let directory = dirs_next::data_local_dir()
+        .ok_or("Failed to locate the local data directory")?
+        .join("comtrya");
+
+    Ok(Value::String(
+        password_in(&directory, &seed, len).map_err(|error| error.to_string())?,
+    ))
+}
+
+fn password_in(directory: &Path, seed: &str, len: usize) -> anyhow::Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&host_secret(directory)?)?;
+    mac.update(seed.as_bytes());
+
+    Ok(ChaCha20Rng::from_seed(mac.finalize().into_bytes().into())
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect())
+}
+
+/// Random bytes only this user on this host can read, created the first
+/// time they're needed
+fn host_secret(directory: &Path) -> anyhow::Result<[u8; SECRET_LEN]> {
+    let path = directory.join(SECRET_FILE);
+    std::fs::create_dir_all(directory)?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    match options.open(&path) {
+        Ok(mut file) => {
+            let mut secret = [0; SECRET_LEN];
+            rand::thread_rng().fill(&mut secret);
+            file.write_all(&secret)?;
+
+            Ok(secret)
+        }
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+            std::fs::read(&path)?.try_into().map_err(|_| {
+                anyhow!(
+                    "{} isn't a valid secret, remove it to make a new one",
+                    path.display()
+                )
+            })
+        }
+        Err(error) => Err(anyhow!("Failed to create {}: {}", path.display(), error)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tera_functions::render;
+    use pretty_assertions::assert_eq;
+    use tera::Context;
+
+    fn render_str(template: &str) -> tera::Result<String> {
+        render(None, template, &Context::new())
+    }
+
+    #[test]
+    fn it_can_use_the_functions() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path();
+        std::fs::write(root.join("b.txt"), "comtrya")?;
+        std::fs::write(root.join("a.txt"), "")?;
+
+        std::env::set_var("COMTRYA_TERA_TEST", "Jaffa");
+
+        assert_eq!(
+            "Jaffa Tau'ri",
+            render_str(
+                r#"{{ env(name="COMTRYA_TERA_TEST") }} {{ env(name="COMTRYA_TERA_UNSET", default="Tau'ri") }}"#
+            )?
+        );
+        assert!(render_str(r#"{{ env(name="COMTRYA_TERA_UNSET") }}"#).is_err());
+
+        assert_eq!(
+            "hello world",
+            render_str(r#"{{ command_output(cmd="echo", args=["hello", "world"]) }}"#)?
+        );
+        assert!(render_str(r#"{{ command_output(cmd="false") }}"#).is_err());
+
+        assert!(render_str(r#"{{ which(name="sh") }}"#)?.ends_with("/sh"));
+        assert_eq!(
+            "",
+            render_str(r#"{{ which(name="comtrya-does-not-exist") }}"#)?
+        );
+
+        assert_eq!(
+            "true false",
+            render_str(&format!(
+                r#"{{{{ path_exists(path="{0}/a.txt") }}}} {{{{ path_exists(path="{0}/c.txt") }}}}"#,
+                root.display()
+            ))?
+        );
+
+        assert_eq!(
+            format!("{0}/a.txt,{0}/b.txt", root.display()),
+            render_str(&format!(
+                r#"{{{{ glob(pattern="{}/*.txt") | join(sep=",") }}}}"#,
+                root.display()
+            ))?
+        );
+
+        let value = render_str(r#"{{ sha256(value="comtrya") }}"#)?;
+        assert_eq!(sha256::digest("comtrya"), value);
+        assert_eq!(
+            value,
+            render_str(&format!(
+                r#"{{{{ sha256(path="{}/b.txt") }}}}"#,
+                root.display()
+            ))?
+        );
+        assert!(render_str(r#"{{ sha256() }}"#).is_err());
+
+        assert!(!render_str("{{ hostname() }}")?.is_empty());
+
+        if let Some(home) = dirs_next::home_dir() {
+            assert_eq!(
+                home.join(".config").display().to_string(),
+                render_str(r#"{{ expand_home(path="~/.config") }}"#)?
+            );
+        }
+
+        assert_eq!(4, render_str(r#"{{ now(format="%Y") }}"#)?.len());
+        assert!(render_str("{{ now(timestamp=true) }}")?
+            .parse::<i64>()
+            .is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_generates_the_same_password_for_the_same_seed() -> anyhow::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let password = password_in(directory.path(), "postgres", 24)?;
+
+        assert_eq!(24, password.len());
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(password, password_in(directory.path(), "postgres", 24)?);
+        assert_ne!(password, password_in(directory.path(), "redis", 24)?);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let secret = std::fs::metadata(directory.path().join(SECRET_FILE))?;
+            assert_eq!(0o600, secret.permissions().mode() & 0o777);
+        }
+
+        // Another host has another secret
+        let other = tempfile::tempdir()?;
+        assert_ne!(password, password_in(other.path(), "postgres", 24)?);
+
+        std::fs::write(directory.path().join(SECRET_FILE), "short")?;
+        assert!(password_in(directory.path(), "postgres", 24).is_err());
+
+        assert!(render_str("{{ random_password() }}").is_err());
+
+        Ok(())
+    }
+}
@@ -1,4 +1,19 @@
-use tera::{Function, Result, Tera, Value};
+mod filters;
+mod functions;
+
+use ignore::WalkBuilder;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+};
+use tera::{Context, Function, Result, Tera, Value};
+
+/// The directory below the root of the manifests with templates to
+/// include, import macros from and extend
+pub const TEMPLATES_DIR: &str = "templates";
+
+static TEMPLATES: OnceLock<Mutex<HashMap<PathBuf, Tera>>> = OnceLock::new();
 
 pub struct ReadFileContents;
 
@@ -23,8 +38,85 @@ impl Function for ReadFileContents {
     }
 }
 
+/// Registers comtrya's functions and filters, which every manifest and
+/// templated file can use
 pub fn register_functions(tera: &mut Tera) {
     tera.register_function("read_file_contents", ReadFileContents);
+    functions::register(tera);
+    filters::register(tera);
+}
+
+/// Renders a template with comtrya's functions. With a `root`, it can
+/// include, import and extend the templates in `<root>/templates`, which are
+/// loaded once per run.
+pub fn render(root: Option<&Path>, template: &str, context: &Context) -> Result<String> {
+    let mut tera = match root {
+        Some(root) => shared(root)?,
+        None => plain(),
+    };
+
+    tera.render_str(template, context)
+}
+
+/// A copy of the templates of `root`, so rendering, which may run commands,
+/// doesn't hold the lock
+fn shared(root: &Path) -> Result<Tera> {
+    let mut instances = TEMPLATES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    match instances.get(root) {
+        Some(tera) => Ok(tera.clone()),
+        None => {
+            let tera = templates(&root.join(TEMPLATES_DIR))?;
+            instances.insert(root.to_path_buf(), tera.clone());
+
+            Ok(tera)
+        }
+    }
+}
+
+/// Tera with comtrya's functions and filters, but no templates
+fn plain() -> Tera {
+    let mut tera = Tera::default();
+
+    // Manifests and the files they copy aren't HTML
+    tera.autoescape_on(vec![]);
+    register_functions(&mut tera);
+
+    tera
+}
+
+/// Every file in `directory`, named after its path below it
+fn templates(directory: &Path) -> Result<Tera> {
+    let mut tera = plain();
+
+    if !directory.is_dir() {
+        return Ok(tera);
+    }
+
+    let files: Vec<(PathBuf, Option<String>)> = WalkBuilder::new(directory)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .filter_map(|entry| {
+            let name = entry
+                .path()
+                .strip_prefix(directory)
+                .ok()?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            Some((entry.into_path(), Some(name)))
+        })
+        .collect();
+
+    tera.add_template_files(files)?;
+
+    Ok(tera)
 }
 
 #[cfg(test)]
@@ -64,4 +156,40 @@ SXOE"#;
 
         Ok(())
     }
+
+    #[test]
+    fn can_use_the_templates_of_the_root() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let templates = root.path().join(TEMPLATES_DIR);
+        std::fs::create_dir_all(templates.join("shell"))?;
+
+        std::fs::write(
+            templates.join("shell").join("header.sh"),
+            "# Managed by comtrya, for {{ user }}",
+        )?;
+        std::fs::write(
+            templates.join("macros.tera"),
+            "{% macro alias(name, command) %}alias {{ name }}='{{ command }}'{% endmacro %}",
+        )?;
+        std::fs::write(
+            templates.join("rc.sh"),
+            "{% include \"shell/header.sh\" %}\n{% block aliases %}{% endblock %}",
+        )?;
+
+        let mut context = Context::new();
+        context.insert("user", "jack");
+
+        let rendered = render(
+            Some(root.path()),
+            r#"{% extends "rc.sh" %}{% import "macros.tera" as macros %}{% block aliases %}{{ macros::alias(name="ll", command="ls -l") }}{% endblock %}"#,
+            &context,
+        )?;
+
+        assert_eq!("# Managed by comtrya, for jack\nalias ll='ls -l'", rendered);
+
+        // Without templates, only the functions are there
+        assert!(render(None, "{% include \"rc.sh\" %}", &context).is_err());
+
+        Ok(())
+    }
 }
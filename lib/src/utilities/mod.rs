@@ -1,11 +1,14 @@
 pub mod lua;
-use std::{ops::Deref, path::PathBuf};
+mod path;
+pub use path::{expand_home, CustomPathBuf};
+use std::cell::Cell;
 
 use crate::contexts::Contexts;
 
-use camino::Utf8PathBuf;
-use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{DeserializeOwned, Error as _, Visitor},
+    forward_to_deserialize_any, Deserializer,
+};
 use which::which;
 
 pub fn get_binary_path(binary: &str) -> Result<String, anyhow::Error> {
@@ -24,23 +27,39 @@ pub fn get_privilege_provider(contexts: &Contexts) -> Option<String> {
     None
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, PartialOrd, Ord)]
-pub struct CustomPathBuf(pub Utf8PathBuf);
+/// The fields `T` is deserialized from, aliases included. Structs with
+/// flattened fields accept any field, so there's nothing to list for them.
+pub(crate) fn struct_fields<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let fields = Cell::new(None);
+    let _ = T::deserialize(StructFields(&fields));
 
-impl JsonSchema for CustomPathBuf {
-    fn schema_name() -> String {
-        String::from("CustomPathBuf")
-    }
+    fields.get()
+}
+
+/// Only records the fields serde asks it for, it never deserializes anything
+struct StructFields<'a>(&'a Cell<Option<&'static [&'static str]>>);
+
+impl<'de> Deserializer<'de> for StructFields<'_> {
+    type Error = serde::de::value::Error;
 
-    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
-        gen.subschema_for::<PathBuf>()
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(Self::Error::custom("not a struct"))
     }
-}
 
-impl Deref for CustomPathBuf {
-    type Target = Utf8PathBuf;
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.set(Some(fields));
+
+        Err(Self::Error::custom("only the fields were needed"))
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
     }
 }
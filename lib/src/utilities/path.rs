@@ -0,0 +1,210 @@
+use crate::manifests::Manifest;
+use anyhow::anyhow;
+use camino::Utf8PathBuf;
+use regex::{Captures, Regex};
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// The `files/` directory next to the manifest
+const FILES_PREFIX: &str = "@files";
+
+/// The directory the manifest is in
+const MANIFEST_PREFIX: &str = "@manifest";
+
+/// `~` or `~/` at the start of `path` is the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path, dirs_next::home_dir()) {
+        ("~", Some(home)) => home,
+        (path, Some(home)) if path.starts_with("~/") => home.join(&path[2..]),
+        (path, _) => PathBuf::from(path),
+    }
+}
+
+/// A path given to an action, as written in the manifest. `~` is the home
+/// directory, `$NAME` and `${NAME}` are environment variables, and paths
+/// starting with `@files/` or `@manifest/` are in the `files/` directory of
+/// the manifest or the directory it's in. See `resolve`.
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, PartialOrd, Ord)]
+pub struct CustomPathBuf(pub Utf8PathBuf);
+
+impl CustomPathBuf {
+    /// The path with its variables, `~` and prefix expanded. Paths that are
+    /// relative after that are left relative.
+    pub fn resolve(&self, manifest: &Manifest) -> anyhow::Result<PathBuf> {
+        let path = expand_variables(self.as_str()).map_err(|name| {
+            anyhow!(
+                "Failed to resolve path {}, environment variable {} isn't set",
+                self,
+                name
+            )
+        })?;
+
+        for (prefix, directory) in [
+            (FILES_PREFIX, manifest.files_dir()),
+            (MANIFEST_PREFIX, manifest.root_dir.clone()),
+        ] {
+            let rest = match path.strip_prefix(prefix) {
+                Some("") => "",
+                Some(rest) => match rest.strip_prefix('/') {
+                    Some(rest) => rest,
+                    None => continue,
+                },
+                None => continue,
+            };
+
+            let directory = directory.ok_or_else(|| {
+                anyhow!(
+                    "Failed to resolve path {}, its manifest isn't in a directory",
+                    self
+                )
+            })?;
+
+            return Ok(match rest.is_empty() {
+                true => directory,
+                false => directory.join(rest),
+            });
+        }
+
+        Ok(expand_home(&path))
+    }
+
+    /// Like `resolve`, but relative paths are relative to `directory`
+    pub fn resolve_in(&self, manifest: &Manifest, directory: &Path) -> anyhow::Result<PathBuf> {
+        Ok(directory.join(self.resolve(manifest)?))
+    }
+}
+
+/// `$NAME` and `${NAME}` replaced by the environment variable, or the name
+/// of the first one that isn't set
+fn expand_variables(path: &str) -> Result<String, String> {
+    static VARIABLE: OnceLock<Regex> = OnceLock::new();
+
+    let variable = VARIABLE.get_or_init(|| {
+        Regex::new(r"\$(?:\{(\w+)\}|(\w+))").expect("Failed to compile the variable regex")
+    });
+
+    let mut unset = None;
+
+    let expanded = variable.replace_all(path, |captures: &Captures| {
+        let name = captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .map(|name| name.as_str())
+            .unwrap_or_default();
+
+        std::env::var(name).unwrap_or_else(|_| {
+            unset.get_or_insert_with(|| name.to_string());
+            String::new()
+        })
+    });
+
+    match unset {
+        Some(name) => Err(name),
+        None => Ok(expanded.to_string()),
+    }
+}
+
+impl JsonSchema for CustomPathBuf {
+    fn schema_name() -> String {
+        String::from("CustomPathBuf")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        gen.subschema_for::<PathBuf>()
+    }
+}
+
+impl Deref for CustomPathBuf {
+    type Target = Utf8PathBuf;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Display for CustomPathBuf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for CustomPathBuf {
+    fn from(path: &str) -> Self {
+        CustomPathBuf(Utf8PathBuf::from(path))
+    }
+}
+
+impl From<String> for CustomPathBuf {
+    fn from(path: String) -> Self {
+        CustomPathBuf(Utf8PathBuf::from(path))
+    }
+}
+
+impl PartialEq<CustomPathBuf> for &str {
+    fn eq(&self, other: &CustomPathBuf) -> bool {
+        *self == other.as_str()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_resolve_paths() -> anyhow::Result<()> {
+        let manifest = Manifest {
+            root_dir: Some(PathBuf::from("/manifests/dev")),
+            ..Default::default()
+        };
+        let resolve = |path: &str| CustomPathBuf::from(path).resolve(&manifest);
+
+        std::env::set_var("COMTRYA_PATH_TEST", "/opt/comtrya");
+
+        assert_eq!(
+            PathBuf::from("/opt/comtrya/bin"),
+            resolve("$COMTRYA_PATH_TEST/bin")?
+        );
+        assert_eq!(
+            PathBuf::from("/opt/comtrya-bin"),
+            resolve("${COMTRYA_PATH_TEST}-bin")?
+        );
+        assert_eq!(
+            PathBuf::from("/manifests/dev/files/gitconfig"),
+            resolve("@files/gitconfig")?
+        );
+        assert_eq!(PathBuf::from("/manifests/dev"), resolve("@manifest")?);
+        assert_eq!(
+            PathBuf::from("/manifests/dev/scripts/setup.sh"),
+            resolve("@manifest/scripts/setup.sh")?
+        );
+        assert_eq!(PathBuf::from("@manifests/x"), resolve("@manifests/x")?);
+        assert_eq!(PathBuf::from("relative/path"), resolve("relative/path")?);
+        assert_eq!(
+            PathBuf::from("/manifests/dev/files/relative/path"),
+            CustomPathBuf::from("relative/path")
+                .resolve_in(&manifest, &PathBuf::from("/manifests/dev/files"))?
+        );
+
+        if let Some(home) = dirs_next::home_dir() {
+            assert_eq!(home.join(".config"), resolve("~/.config")?);
+            assert_eq!(home, resolve("~")?);
+        }
+
+        assert_eq!(
+            "Failed to resolve path $COMTRYA_PATH_UNSET/bin, environment variable COMTRYA_PATH_UNSET isn't set",
+            resolve("$COMTRYA_PATH_UNSET/bin").unwrap_err().to_string()
+        );
+        assert!(CustomPathBuf::from("@files/x")
+            .resolve(&Manifest::default())
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     cmp::Ordering,
+    collections::BTreeMap,
     ffi::OsString,
     fmt::{Debug, Display},
     path::PathBuf,
@@ -8,7 +9,7 @@ use std::{
 use serde_json::Value as JsonValue;
 
 use serde::{
-    de::{Error as SError, SeqAccess, Visitor},
+    de::{Error as SError, MapAccess, SeqAccess, Visitor},
     Deserialize, Deserializer, Serialize,
 };
 
@@ -18,6 +19,7 @@ pub enum Value {
     String(String),
     Number(Number),
     List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
 }
 
 #[derive(Clone, PartialEq, PartialOrd)]
@@ -42,6 +44,7 @@ impl Serialize for Value {
             Value::Number(n) => n.serialize(serializer),
             Value::String(s) => serializer.serialize_str(s),
             Value::List(seq) => seq.serialize(serializer),
+            Value::Map(map) => map.serialize(serializer),
         }
     }
 }
@@ -149,6 +152,19 @@ impl<'de> Deserialize<'de> for Value {
 
                 Ok(Value::List(vec))
             }
+
+            fn visit_map<V>(self, mut visitor: V) -> Result<Value, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let mut map = BTreeMap::new();
+
+                while let Some((key, value)) = visitor.next_entry()? {
+                    map.insert(key, value);
+                }
+
+                Ok(Value::Map(map))
+            }
         }
 
         deserializer.deserialize_any(ValueVisitor)
@@ -165,6 +181,10 @@ impl Debug for Value {
                 formatter.write_str("List ")?;
                 formatter.debug_list().entries(list).finish()
             }
+            Value::Map(map) => {
+                formatter.write_str("Map ")?;
+                formatter.debug_map().entries(map).finish()
+            }
         }
     }
 }
@@ -351,6 +371,16 @@ impl<T: Into<Value>> From<Vec<T>> for Value {
     }
 }
 
+impl<T: Into<Value>> From<BTreeMap<String, T>> for Value {
+    fn from(from: BTreeMap<String, T>) -> Self {
+        Value::Map(
+            from.into_iter()
+                .map(|(key, value)| (key, value.into()))
+                .collect(),
+        )
+    }
+}
+
 impl TryFrom<JsonValue> for Value {
     type Error = anyhow::Error;
 
@@ -398,11 +428,9 @@ impl TryFrom<JsonValue> for Value {
                     .filter_map(Result::ok)
                     .collect(),
             ),
-            JsonValue::Object(o) => Self::List(
-                o.values()
-                    .cloned()
-                    .map(TryInto::try_into)
-                    .filter_map(Result::ok)
+            JsonValue::Object(o) => Self::Map(
+                o.into_iter()
+                    .filter_map(|(key, value)| value.try_into().ok().map(|value| (key, value)))
                     .collect(),
             ),
         };
@@ -425,6 +453,11 @@ impl Display for Value {
                     .map(|value| value.to_string())
                     .collect::<Vec<String>>()
                     .join(","),
+                Value::Map(map) => map
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<String>>()
+                    .join(","),
             }
         )
     }
@@ -432,7 +465,7 @@ impl Display for Value {
 
 #[cfg(test)]
 mod test {
-    use std::{borrow::Cow, ffi::OsString, path::PathBuf};
+    use std::{borrow::Cow, collections::BTreeMap, ffi::OsString, path::PathBuf};
 
     use crate::values::{Number, NumberVariant, Value};
     use anyhow::Ok;
@@ -479,6 +512,62 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn from_map_test() -> anyhow::Result<()> {
+        let map = BTreeMap::from([
+            (String::from("pilot"), vec!["Teyla Emmagan"]),
+            (String::from("medic"), vec!["Carson Beckett"]),
+        ]);
+
+        assert_eq!(
+            Value::from(map),
+            Value::Map(BTreeMap::from([
+                (
+                    String::from("medic"),
+                    Value::List(vec![Value::String("Carson Beckett".to_string())])
+                ),
+                (
+                    String::from("pilot"),
+                    Value::List(vec![Value::String("Teyla Emmagan".to_string())])
+                ),
+            ]))
+        );
+
+        assert_eq!(
+            Value::from(BTreeMap::from([(String::from("team"), "SGA-1")])).to_string(),
+            "team=SGA-1".to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_json_object_test() -> anyhow::Result<()> {
+        let value = Value::try_from(serde_json::json!({
+            "leader": "John Sheppard",
+            "team": ["Teyla Emmagan", "Ronon Dex"],
+        }))?;
+
+        assert_eq!(
+            value,
+            Value::Map(BTreeMap::from([
+                (
+                    String::from("leader"),
+                    Value::String("John Sheppard".to_string())
+                ),
+                (
+                    String::from("team"),
+                    Value::List(vec![
+                        Value::String("Teyla Emmagan".to_string()),
+                        Value::String("Ronon Dex".to_string())
+                    ])
+                ),
+            ]))
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn number_compare_test() -> anyhow::Result<()> {
         // unsigned